@@ -33,6 +33,55 @@
 // * CHANGE LOG
 // ***************************************************************************************
 // * 04/10/2025 GJM - New Program. Version 1.0
+// * 10/19/2026 Contributors - Extensions to the simulator:
+// *                - Added "project" what-if command and --difficulty option.
+// *                - Added multi-segment burn schedules ("10 200 -5; 15 120 0").
+// *                - Added conditional "until" burns (e.g. "until vdown<=50 vburn=200").
+// *                - Added named commands (burn, abort, status, help, hold, repeat and
+// *                  relative adjustments) with field-specific error messages.
+// *                - Added hover burn rate, thrust-to-weight and delta-v readouts; "hold"
+// *                  now auto-throttles to the hover rate as fuel burns.
+// *                - Added pitch attitude dynamics: the RCS slews the LEM to a commanded
+// *                  pitch and the descent engine thrust is resolved through that angle.
+// *                - Added optional DPS engine model (--engine-model) with the throttle
+// *                  envelope, minimum throttle and ignition/shutdown state.
+// *                - Added throttle response lag, ignition delay and shutdown tail-off.
+// *                - hBurn and attitude control now draw on a separate RCS propellant
+// *                  budget with its own specific impulse and quad layout.
+// *                - Added a crossrange axis (cPos, cSpeed) with its own cBurn; landings
+// *                  are judged on the total horizontal speed.
+// *                - Added scenarios (--scenario) with a target landing site, distance to
+// *                  go, Mission Control position advice and a landing accuracy grade.
+// *                - Added procedural terrain (--terrain, --seed) with craters, boulders
+// *                  and slopes; touchdown on steep slopes or boulders tips or damages the LEM.
+// *                - Added elevation grid terrain (--dem, ESRI ASCII grid or GeoTIFF) and the
+// *                  tranquility and ocean-of-storms scenarios with bundled tiles.
+// *                - Added the Landing Point Designator ("lpd") with a hazard map and site
+// *                  redesignation in 100 ft clicks, with the fuel cost reported.
+// *                - Added mascon gravity anomalies (--mascon) to the flight and projections.
+// *                - Added the pdi scenario, a powered descent from orbit on a spherical Moon,
+// *                  and an autopilot ("auto") that flies the braking phase to high gate.
+// *                - Added a pendulum propellant slosh model (--slosh) that pushes the LEM
+// *                  and disturbs the fuel quantity reading.
+// *                - Added propellant gauging (--gauging): indicated fuel with bias and noise,
+// *                  a low-level light and Mission Control "60/30 seconds" bingo callouts.
+// *                - Added a landing radar (--radar) that locks on below 40,000 ft and feeds
+// *                  the displayed altitude and vDown, with measurement noise and dropouts.
+// *                - Added IMU navigation (--nav): a Kalman filter blends drifting accelerometers
+// *                  with radar updates, a flight recorder (--record) and a navigation debrief.
+// *                - Added touchdown dynamics: contact light from the 67-inch probes, footpad
+// *                  sinkage, honeycomb strut stroke and a tip-over check from speed and slope.
+// *                - Added the engine stop at contact light ("stop"): burns are interrupted at
+// *                  contact, and touchdown grades the engine run after contact (nozzle damage).
+// *                - Added lunar dust (--dust): below 100 ft the displayed horizontal speed and
+// *                  position lose precision, depending on altitude and thrust.
+// *                - Added failure injection: --fail schedules a DPS thrust loss, stuck
+// *                  throttle, radar failure, RCS jet failure or loss of communications
+// *                  at a time or altitude, and --random-failures lets them happen at
+// *                  random. Alarms are logged and printed in a flight log after landing.
+// *                - Added the program-alarms scenario: the AGC flies the approach from high
+// *                  gate and raises 1201/1202 program alarms, Mission Control calls GO or
+// *                  NO-GO after the usual delay, and a NO-GO hands the LEM to the pilot.
 // ***************************************************************************************
 // * Copyright (C)2025 by George McGinn.  All Rights Reserved
 // *
@@ -42,10 +91,26 @@
 // ***************************************************************************************
 // */
 
-use std::io::{self, Write};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
+// Physical constants and vehicle parameters shared by the flight loop and projections
+const GRAVITY: f64 = 5.33136483;        // ft/s^2 (lunar gravity)
+const STANDARD_GRAVITY: f64 = 32.174;   // ft/s^2 (standard gravity)
+const VEX: f64 = 10000.0;               // ft/s (exhaust velocity)
+const MAX_VERTICAL_BURN: f64 = 250.0;   // lbs/s
+const DPS_MAX_BURN: f64 = 10500.0 * STANDARD_GRAVITY / VEX;  // lbs/s (about 33.8; the real DPS's 10,500 lbf full thrust)
+const MAX_HORIZ_BURN: f64 = 10.0;       // lbs/s
+const DESCENT_DRY_MASS: f64 = 4700.0;   // lb (descent stage dry)
+const ASCENT_DRY_MASS: f64 = 4850.0;    // lb (ascent stage dry)
+const ASCENT_FUEL_MASS: f64 = 5187.0;   // lb (ascent fuel)
+const FEET_PER_METER: f64 = 3.28084;
+const MOON_RADIUS: f64 = 5_700_131.0;    // ft (mean lunar radius, 1,737.4 km)
+
 // Simple pseudo-random number generator to replace rand crate
 struct SimpleRng {
     seed: u64,
@@ -60,113 +125,2890 @@ impl SimpleRng {
         SimpleRng { seed }
     }
 
+    // Fixed seed (--seed) so a flight and its terrain can be repeated
+    fn with_seed(seed: u64) -> SimpleRng {
+        SimpleRng { seed }
+    }
+
     // Linear Congruential Generator (LCG) for pseudo-random numbers
     fn next(&mut self) -> u32 {
         self.seed = self.seed.wrapping_mul(6364136223846793005).wrapping_add(1);
         (self.seed >> 33) as u32
     }
 
-    fn gen_range(&mut self, range: std::ops::RangeInclusive<i32>) -> i32 {
-        let min = *range.start();
-        let max = *range.end();
-        min + (self.next() % (max - min + 1) as u32) as i32
-    }
+    fn gen_range(&mut self, range: std::ops::RangeInclusive<i32>) -> i32 {
+        let min = *range.start();
+        let max = *range.end();
+        min + (self.next() % (max - min + 1) as u32) as i32
+    }
+
+    // Uniform value in [0, 1)
+    fn gen_f64(&mut self) -> f64 {
+        self.next() as f64 / (1u64 << 31) as f64
+    }
+
+    // Normally distributed value with mean 0 and standard deviation 1 (Box-Muller)
+    fn gen_normal(&mut self) -> f64 {
+        let u = 1.0 - self.gen_f64();  // (0, 1], so the logarithm is finite
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * self.gen_f64()).cos()
+    }
+}
+
+// RK4 helper functions 
+fn compute_vertical_derivatives(state: [f64; 2], net_acc: f64) -> [f64; 2] {
+    let mut derivatives = [0.0; 2];
+    derivatives[0] = -state[1];  // d(altitude)/dt = -vDown
+    derivatives[1] = net_acc;    // d(vDown)/dt = netAcc
+    derivatives
+}
+
+fn compute_horizontal_derivatives(state: [f64; 2], horiz_acc: f64) -> [f64; 2] {
+    let mut derivatives = [0.0; 2];
+    derivatives[0] = state[1];   // d(horizPos)/dt = horizSpeed
+    derivatives[1] = horiz_acc;  // d(horizSpeed)/dt = horizAcc
+    derivatives
+}
+
+fn rk4_vertical(altitude: &mut f64, v_down: &mut f64, dt: f64, net_acc: f64) {
+    let state = [*altitude, *v_down];
+    let k1 = compute_vertical_derivatives(state, net_acc);
+    let temp_state = [state[0] + 0.5 * dt * k1[0], state[1] + 0.5 * dt * k1[1]];
+    let k2 = compute_vertical_derivatives(temp_state, net_acc);
+    let temp_state = [state[0] + 0.5 * dt * k2[0], state[1] + 0.5 * dt * k2[1]];
+    let k3 = compute_vertical_derivatives(temp_state, net_acc);
+    let temp_state = [state[0] + dt * k3[0], state[1] + dt * k3[1]];
+    let k4 = compute_vertical_derivatives(temp_state, net_acc);
+
+    *altitude += (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]) * dt / 6.0;
+    *v_down += (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]) * dt / 6.0;
+}
+
+fn rk4_horizontal(horiz_pos: &mut f64, horiz_speed: &mut f64, dt: f64, horiz_acc: f64) {
+    let state = [*horiz_pos, *horiz_speed];
+    let k1 = compute_horizontal_derivatives(state, horiz_acc);
+    let temp_state = [state[0] + 0.5 * dt * k1[0], state[1] + 0.5 * dt * k1[1]];
+    let k2 = compute_horizontal_derivatives(temp_state, horiz_acc);
+    let temp_state = [state[0] + 0.5 * dt * k2[0], state[1] + 0.5 * dt * k2[1]];
+    let k3 = compute_horizontal_derivatives(temp_state, horiz_acc);
+    let temp_state = [state[0] + dt * k3[0], state[1] + dt * k3[1]];
+    let k4 = compute_horizontal_derivatives(temp_state, horiz_acc);
+
+    *horiz_pos += (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]) * dt / 6.0;
+    *horiz_speed += (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]) * dt / 6.0;
+}
+
+fn compute_attitude_derivatives(state: [f64; 2], pitch_acc: f64) -> [f64; 2] {
+    let mut derivatives = [0.0; 2];
+    derivatives[0] = state[1];   // d(pitch)/dt = pitchRate
+    derivatives[1] = pitch_acc;  // d(pitchRate)/dt = torque / inertia
+    derivatives
+}
+
+fn rk4_attitude(pitch: &mut f64, pitch_rate: &mut f64, dt: f64, pitch_acc: f64) {
+    let state = [*pitch, *pitch_rate];
+    let k1 = compute_attitude_derivatives(state, pitch_acc);
+    let temp_state = [state[0] + 0.5 * dt * k1[0], state[1] + 0.5 * dt * k1[1]];
+    let k2 = compute_attitude_derivatives(temp_state, pitch_acc);
+    let temp_state = [state[0] + 0.5 * dt * k2[0], state[1] + 0.5 * dt * k2[1]];
+    let k3 = compute_attitude_derivatives(temp_state, pitch_acc);
+    let temp_state = [state[0] + dt * k3[0], state[1] + dt * k3[1]];
+    let k4 = compute_attitude_derivatives(temp_state, pitch_acc);
+
+    *pitch += (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]) * dt / 6.0;
+    *pitch_rate += (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]) * dt / 6.0;
+}
+
+// Vertical burn rate whose thrust exactly balances lunar gravity for the given mass
+fn hover_burn_rate(mass: f64) -> f64 {
+    mass * GRAVITY / VEX  // lbs/s (thrust = vBurn * vex / g0 = weight = mass * gravity / g0)
+}
+
+// Thrust-to-weight ratio (lunar weight) for a vertical burn rate
+fn thrust_to_weight(v_burn: f64, mass: f64) -> f64 {
+    v_burn / hover_burn_rate(mass)
+}
+
+// Delta-v left in the descent stage (rocket equation)
+fn descent_delta_v(mass: f64, descent_fuel_mass: f64) -> f64 {
+    VEX * (mass / (mass - descent_fuel_mass)).ln()  // ft/s
+}
+
+// Vehicle parameters beyond the basic mass and engine constants
+struct VehicleConfig {
+    pitch_inertia:       f64,  // slug-ft^2 (pitch moment of inertia)
+    rcs_pitch_torque:    f64,  // ft-lbf (maximum RCS pitch torque)
+    max_pitch_rate:      f64,  // deg/s (attitude-hold rate limit)
+    max_pitch:           f64,  // deg (largest pitch the pilot may command)
+    dps_envelope:        bool, // enforce the DPS throttle envelope (--engine-model)
+    dps_min_throttle:    f64,  // fraction of DPS full thrust (lowest setting once ignited)
+    dps_forbidden_low:   f64,  // fraction of DPS full thrust (start of the unusable band)
+    dps_forbidden_high:  f64,  // fraction of DPS full thrust (full-thrust region begins)
+    throttle_tau:        f64,  // s (first-order throttle time constant; 0 = instantaneous)
+    ignition_delay:      f64,  // s (from a non-zero command until thrust starts building)
+    tailoff_tau:         f64,  // s (thrust decay time constant after shutdown)
+    rcs_propellant:      f64,  // lbs (RCS tanks, separate from the descent propellant)
+    rcs_isp:             f64,  // s (RCS specific impulse)
+    rcs_jet_flow:        f64,  // lbs/s (propellant flow of one translation jet at full hBurn)
+    rcs_moment_arm:      f64,  // ft (distance from the pitch jets to the center of mass)
+    slosh:               bool, // model propellant slosh (--slosh)
+    slosh_fraction:      f64,  // fraction of the descent propellant free to slosh
+    slosh_length:        f64,  // ft (equivalent pendulum length)
+    slosh_damping:       f64,  // damping ratio (tank baffles)
+    slosh_gauge_gain:    f64,  // gauge error per lb of sloshing propellant, times sin(slosh angle)
+    radar_lock_altitude: f64,  // ft (highest altitude above the terrain the landing radar locks on)
+    radar_lock_rate:     f64,  // ft/s (fastest descent rate the landing radar can track)
+    radar_noise:         f64,  // fraction of the measured value (standard deviation, plus a floor)
+    radar_dropout_rate:  f64,  // dropouts per second while locked
+    imu_bias:            f64,  // ft/s^2 (accelerometer bias, standard deviation per axis)
+    imu_scale:           f64,  // fraction (accelerometer scale-factor error, standard deviation)
+    imu_drift:           f64,  // ft/s^2 per root second (bias random walk)
+    imu_noise:           f64,  // ft/s^2 (white noise on each accelerometer sample)
+    nav_position_error:  f64,  // ft (standard deviation of the initial position estimate)
+    nav_speed_error:     f64,  // ft/s (standard deviation of the initial velocity estimate)
+    gauge_bias:          f64,  // fraction of the full load (largest calibration error, --gauging)
+    gauge_noise:         f64,  // lbs (standard deviation of each quantity reading)
+    low_level_fraction:  f64,  // fraction of the full load where the low-level sensor uncovers
+}
+
+impl VehicleConfig {
+    fn lunar_module() -> VehicleConfig {
+        VehicleConfig {
+            pitch_inertia: 30000.0,    // slug-ft^2 (LM with descent stage, approximate)
+            rcs_pitch_torque: 2200.0,  // ft-lbf (four 100 lbf jets on a ~5.5 ft arm)
+            max_pitch_rate: 10.0,      // deg/s
+            max_pitch: 60.0,           // deg
+            dps_envelope: false,
+            dps_min_throttle: 0.10,    // 10%
+            dps_forbidden_low: 0.65,   // 65%
+            dps_forbidden_high: 0.925, // 92.5%
+            throttle_tau: 0.0,         // s (set by --engine-model or --throttle-tau)
+            ignition_delay: 0.5,       // s
+            tailoff_tau: 0.2,          // s
+            rcs_propellant: 633.0,     // lbs (systems A and B combined)
+            rcs_isp: 290.0,            // s
+            rcs_jet_flow: 5.0,         // lbs/s (two jets per direction give the 10 lbs/s hBurn limit)
+            rcs_moment_arm: 5.5,       // ft
+            slosh: false,
+            slosh_fraction: 0.2,       // 20%
+            slosh_length: 3.0,         // ft (about 0.3 Hz at lunar hover thrust)
+            slosh_damping: 0.05,
+            slosh_gauge_gain: 0.5,
+            radar_lock_altitude: 40000.0, // ft
+            radar_lock_rate: 500.0,    // ft/s
+            radar_noise: 0.01,         // 1% (plus 1 ft or 0.5 ft/s)
+            radar_dropout_rate: 0.02,  // about one every 50 s
+            imu_bias: 0.002,           // ft/s^2 (about 60 micro-g)
+            imu_scale: 0.0002,         // 200 ppm
+            imu_drift: 0.0001,         // ft/s^2 per root second
+            imu_noise: 0.01,           // ft/s^2
+            nav_position_error: 300.0, // ft
+            nav_speed_error: 2.0,      // ft/s
+            gauge_bias: 0.01,          // 1%
+            gauge_noise: 3.0,          // lbs
+            low_level_fraction: 0.056, // 5.6% (Apollo low-level sensor)
+        }
+    }
+}
+
+// Highest vBurn the descent engine can fly: the DPS full thrust with --engine-model
+fn max_v_burn(vehicle: &VehicleConfig) -> f64 {
+    if vehicle.dps_envelope { DPS_MAX_BURN } else { MAX_VERTICAL_BURN }
+}
+
+// Check a commanded vBurn against the DPS throttle envelope. Returns the rate to fly and a
+// note if it was clamped, or the reason it was rejected.
+fn throttle_envelope(v_burn: f64, vehicle: &VehicleConfig) -> Result<(f64, Option<String>), String> {
+    let min_rate = vehicle.dps_min_throttle * DPS_MAX_BURN;
+    let forbidden_low = vehicle.dps_forbidden_low * DPS_MAX_BURN;
+    let forbidden_high = vehicle.dps_forbidden_high * DPS_MAX_BURN;
+    if !vehicle.dps_envelope || v_burn == 0.0 {
+        return Ok((v_burn, None));
+    }
+    if v_burn > DPS_MAX_BURN {
+        return Ok((DPS_MAX_BURN, Some(format!(
+            "vBurn {:.2} is above the DPS full thrust; clamped to {:.2} lbs/s.", v_burn, DPS_MAX_BURN))));
+    }
+    if v_burn < min_rate {
+        return Ok((min_rate, Some(format!(
+            "vBurn {:.2} is below the DPS minimum throttle of {:.0}% once ignited; clamped to {:.2} lbs/s (use vBurn 0 to shut down).",
+            v_burn, vehicle.dps_min_throttle * 100.0, min_rate))));
+    }
+    if v_burn > forbidden_low && v_burn < forbidden_high {
+        return Err(format!(
+            "vBurn {:.2} ({:.1}%) is in the DPS forbidden throttle zone ({:.0}% to {:.1}%). Use at most {:.2} or at least {:.2} lbs/s.",
+            v_burn, v_burn / DPS_MAX_BURN * 100.0, vehicle.dps_forbidden_low * 100.0,
+            vehicle.dps_forbidden_high * 100.0, forbidden_low, forbidden_high));
+    }
+    Ok((v_burn, None))
+}
+
+// Nearest vBurn the DPS can actually hold (used for automatic throttling and Mission Control advice)
+fn clamp_to_envelope(v_burn: f64, vehicle: &VehicleConfig) -> f64 {
+    if !vehicle.dps_envelope || v_burn <= 0.0 {
+        return v_burn;
+    }
+    let forbidden_low = vehicle.dps_forbidden_low * DPS_MAX_BURN;
+    let forbidden_high = vehicle.dps_forbidden_high * DPS_MAX_BURN;
+    if v_burn > forbidden_low && v_burn < forbidden_high {
+        if v_burn - forbidden_low < forbidden_high - v_burn { forbidden_low } else { forbidden_high }
+    } else {
+        v_burn.clamp(vehicle.dps_min_throttle * DPS_MAX_BURN, DPS_MAX_BURN)
+    }
+}
+
+// Direction of the force produced by an RCS jet
+#[derive(Clone, Copy, PartialEq)]
+enum JetDirection {
+    Up,
+    Down,
+    PlusX,   // toward +hPos (downrange)
+    MinusX,
+    PlusY,   // crossrange
+    MinusY,
+}
+
+// RCS quad layout: each of the four quads has an up and a down jet (pitch control) and two
+// horizontal jets, so every translation direction is served by jets on two different quads
+const RCS_JETS: [(usize, JetDirection); 16] = [
+    (1, JetDirection::Up), (1, JetDirection::Down), (1, JetDirection::PlusX),  (1, JetDirection::PlusY),
+    (2, JetDirection::Up), (2, JetDirection::Down), (2, JetDirection::MinusX), (2, JetDirection::PlusY),
+    (3, JetDirection::Up), (3, JetDirection::Down), (3, JetDirection::MinusX), (3, JetDirection::MinusY),
+    (4, JetDirection::Up), (4, JetDirection::Down), (4, JetDirection::PlusX),  (4, JetDirection::MinusY),
+];
+
+// Sloshing propellant modelled as a pendulum hanging against the thrust, in the pitch plane (x)
+// and the crossrange plane (y); angles are measured from the thrust axis
+#[derive(Clone, Copy, Default)]
+struct SloshState {
+    angle: (f64, f64),  // rad (positive toward +hPos / +cPos)
+    rate:  (f64, f64),  // rad/s
+}
+
+// RCS propellant and jet health
+#[derive(Clone, Copy)]
+struct RcsState {
+    propellant: f64,         // lbs
+    jet_failed: [bool; 16],  // indexed like RCS_JETS
+}
+
+impl RcsState {
+    fn working_jets(&self, direction: JetDirection) -> usize {
+        RCS_JETS.iter().zip(self.jet_failed.iter())
+            .filter(|((_, jet_direction), failed)| *jet_direction == direction && !**failed)
+            .count()
+    }
+
+    // Largest translation burn (lbs/s) the working jets can deliver in one direction
+    fn translation_capacity(&self, direction: JetDirection, vehicle: &VehicleConfig) -> f64 {
+        self.working_jets(direction) as f64 * vehicle.rcs_jet_flow
+    }
+
+    // Fraction of the pitch jets still working
+    fn pitch_authority(&self) -> f64 {
+        (self.working_jets(JetDirection::Up) + self.working_jets(JetDirection::Down)) as f64 / 8.0
+    }
+}
+
+// Dynamic state of the LEM during the descent
+#[derive(Clone, Copy)]
+struct LanderState {
+    current_time:      f64,  // s
+    altitude:          f64,  // ft
+    v_down:            f64,  // ft/s (downward positive)
+    horiz_pos:         f64,  // ft (downrange)
+    horiz_speed:       f64,  // ft/s (downrange)
+    cross_pos:         f64,  // ft (crossrange, positive to the right)
+    cross_speed:       f64,  // ft/s (crossrange)
+    descent_fuel_mass: f64,  // lbs
+    mass:              f64,  // lbs (both stages)
+    pitch:             f64,  // rad (positive tilts DPS thrust toward +horizPos)
+    pitch_rate:        f64,  // rad/s
+    pitch_command:     f64,  // rad (attitude the RCS is driving toward)
+    dps_ignited:       bool, // descent engine running (tracked with --engine-model)
+    dps_level:         f64,  // lbs/s (burn rate the engine is actually delivering)
+    dps_ignition_time: f64,  // s since a non-zero command found the engine unlit
+    dps_lit:           bool, // combustion established (ignition delay has passed)
+    rcs:               RcsState,
+    slosh:             SloshState,
+    specific_force:    [f64; 3],  // ft/s^2 (up, downrange, crossrange thrust and slosh acceleration last step)
+}
+
+// First-order throttle response with ignition delay and shutdown tail-off. Returns the
+// vBurn actually delivered this step; without a time constant the command is followed exactly.
+fn engine_response(lander: &mut LanderState, vehicle: &VehicleConfig, commanded: f64, dt: f64) -> f64 {
+    if vehicle.throttle_tau <= 0.0 {
+        lander.dps_level = commanded;
+        lander.dps_lit = commanded > 0.0;
+        return commanded;
+    }
+    if commanded > 0.0 && !lander.dps_lit {
+        lander.dps_ignition_time += dt;
+        if lander.dps_ignition_time >= vehicle.ignition_delay {
+            lander.dps_lit = true;
+        }
+    } else if commanded <= 0.0 {
+        lander.dps_ignition_time = 0.0;
+        lander.dps_lit = false;
+    }
+    let target = if lander.dps_lit { commanded } else { 0.0 };
+    let tau = if target == 0.0 { vehicle.tailoff_tau } else { vehicle.throttle_tau };
+    lander.dps_level += (target - lander.dps_level) * (1.0 - (-dt / tau).exp());
+    if lander.descent_fuel_mass <= 0.0 {
+        lander.dps_level = 0.0;  // no propellant, no thrust
+    }
+    lander.dps_level
+}
+
+// RCS attitude hold: command a pitch rate toward the target and fire torque to reach it
+fn rcs_pitch_torque(lander: &LanderState, vehicle: &VehicleConfig) -> f64 {
+    let max_rate = vehicle.max_pitch_rate.to_radians();
+    let desired_rate = (1.0 * (lander.pitch_command - lander.pitch)).clamp(-max_rate, max_rate);  // 1/s attitude gain
+    let torque = vehicle.pitch_inertia * 2.0 * (desired_rate - lander.pitch_rate);  // 2/s rate gain
+    let max_torque = vehicle.rcs_pitch_torque * lander.rcs.pitch_authority();
+    torque.clamp(-max_torque, max_torque)
+}
+
+// Total horizontal speed over the surface (downrange and crossrange combined)
+fn ground_speed(lander: &LanderState) -> f64 {
+    lander.horiz_speed.hypot(lander.cross_speed)  // ft/s
+}
+
+// Swing the slosh pendulums for one step. The specific force (thrust per unit mass, axial and
+// lateral in the LEM's frame) sets where they hang and how fast they swing, and the LEM's own pitch
+// acceleration swings the x pendulum the other way. Returns the lateral accelerations (ft/s^2) the
+// swinging propellant puts on the LEM beyond a rigid load.
+fn step_slosh(lander: &mut LanderState, vehicle: &VehicleConfig, axial: f64, lateral: (f64, f64), pitch_acc: f64,
+              dt: f64) -> (f64, f64) {
+    let slosh_mass = vehicle.slosh_fraction * lander.descent_fuel_mass;  // lbs
+    let force = axial.hypot(lateral.0).hypot(lateral.1);  // ft/s^2
+    let omega = (force / vehicle.slosh_length).sqrt();  // rad/s
+    let equilibrium = (-lateral.0.atan2(axial.max(0.0)), -lateral.1.atan2(axial.max(0.0)));  // lags the push
+    let slosh = &mut lander.slosh;
+    // Semi-implicit Euler is stable here as long as omega * dt stays below 2
+    slosh.rate.0 += (-omega * omega * (slosh.angle.0 - equilibrium.0).sin() - 2.0 * vehicle.slosh_damping * omega * slosh.rate.0
+        - pitch_acc) * dt;
+    slosh.rate.1 += (-omega * omega * (slosh.angle.1 - equilibrium.1).sin() - 2.0 * vehicle.slosh_damping * omega * slosh.rate.1) * dt;
+    // The tank walls stop the swing at 60 degrees
+    let wall = 60f64.to_radians();
+    slosh.angle.0 += slosh.rate.0 * dt;
+    slosh.angle.1 += slosh.rate.1 * dt;
+    if slosh.angle.0.abs() > wall {
+        slosh.angle.0 = slosh.angle.0.clamp(-wall, wall);
+        slosh.rate.0 = 0.0;
+    }
+    if slosh.angle.1.abs() > wall {
+        slosh.angle.1 = slosh.angle.1.clamp(-wall, wall);
+        slosh.rate.1 = 0.0;
+    }
+    let share = slosh_mass / lander.mass * force;
+    (share * (slosh.angle.0 - equilibrium.0).sin(), share * (slosh.angle.1 - equilibrium.1).sin())
+}
+
+// Descent propellant quantity as the gauging probes read it: sloshing propellant piles up on one
+// side of the tanks and moves the level the probes see
+fn fuel_reading(lander: &LanderState, vehicle: &VehicleConfig) -> f64 {
+    if !vehicle.slosh {
+        return lander.descent_fuel_mass;
+    }
+    let slosh_mass = vehicle.slosh_fraction * lander.descent_fuel_mass;  // lbs
+    (lander.descent_fuel_mass + vehicle.slosh_gauge_gain * slosh_mass * lander.slosh.angle.0.sin()).max(0.0)  // lbs
+}
+
+// Propellant quantity gauging system (--gauging): the crew and Mission Control see an indicated
+// quantity with a calibration bias and noise instead of the true descent propellant
+struct FuelGauge {
+    enabled:   bool,
+    full_load: f64,        // lbs (descent propellant at the start)
+    bias:      f64,        // lbs (calibration error, fixed for the flight)
+    rng:       SimpleRng,  // reading noise
+    low_level: bool,       // low-level light on (it latches)
+    callouts:  Vec<f64>,   // seconds (bingo callouts Mission Control still has to make)
+}
+
+impl FuelGauge {
+    fn new(enabled: bool, full_load: f64, vehicle: &VehicleConfig, rng: &mut SimpleRng) -> FuelGauge {
+        FuelGauge {
+            enabled,
+            full_load,
+            bias: (2.0 * rng.gen_f64() - 1.0) * vehicle.gauge_bias * full_load,
+            rng: SimpleRng::with_seed(rng.next() as u64),
+            low_level: false,
+            callouts: vec![60.0, 30.0],
+        }
+    }
+
+    // Indicated descent propellant (lbs)
+    fn read(&mut self, lander: &LanderState, vehicle: &VehicleConfig) -> f64 {
+        let reading = fuel_reading(lander, vehicle);
+        if !self.enabled {
+            return reading;
+        }
+        (reading + self.bias + vehicle.gauge_noise * self.rng.gen_normal()).max(0.0)
+    }
+
+    // The low-level sensor is a point sensor in the tank: it trips when the propellant (sloshed
+    // level included) drops below it, whatever the gauge says
+    fn low_level_tripped(&mut self, lander: &LanderState, vehicle: &VehicleConfig) -> bool {
+        if !self.enabled || self.low_level {
+            return false;
+        }
+        self.low_level = fuel_reading(lander, vehicle) <= vehicle.low_level_fraction * self.full_load;
+        self.low_level
+    }
+}
+
+// Landing radar (--radar): once locked on it measures the height above the terrain below and
+// the descent rate, and the state line shows its last measurement instead of the true state
+struct LandingRadar {
+    enabled: bool,
+    locked:  bool,
+    dropout: f64,                 // s (left in the current dropout)
+    reading: Option<(f64, f64)>,  // ft, ft/s (last altitude and vDown measured)
+    velocity: (f64, f64),         // ft/s (downrange and crossrange speed from the velocity beams)
+    fresh:   bool,                // a measurement was made this cycle
+    failed:  bool,                // radar failure (no data for the rest of the flight)
+    rng:     SimpleRng,           // noise and dropouts
+}
+
+impl LandingRadar {
+    fn new(enabled: bool, rng: &mut SimpleRng) -> LandingRadar {
+        LandingRadar {
+            enabled, locked: false, dropout: 0.0, reading: None, velocity: (0.0, 0.0), fresh: false, failed: false,
+            rng: SimpleRng::with_seed(rng.next() as u64),
+        }
+    }
+
+    // One radar cycle of dt seconds; returns a message when the lock is gained or lost
+    fn update(&mut self, lander: &LanderState, vehicle: &VehicleConfig, terrain: &Terrain, dt: f64) -> Option<String> {
+        self.fresh = false;
+        if !self.enabled || self.failed {
+            self.locked = false;
+            return None;
+        }
+        if self.dropout > 0.0 {
+            self.dropout -= dt;
+            return None;
+        }
+        if self.locked && self.rng.gen_f64() < vehicle.radar_dropout_rate * dt {
+            self.locked = false;
+            self.dropout = 1.0 + 2.0 * self.rng.gen_f64();  // 1 to 3 s
+            return Some(format!("\x1b[33mLanding radar dropout at t={:.2}s (about {:.1} s).\x1b[0m", lander.current_time, self.dropout));
+        }
+        let height = height_above_terrain(lander, terrain);
+        let in_range = height <= vehicle.radar_lock_altitude && lander.v_down.abs() <= vehicle.radar_lock_rate;
+        let message = if in_range && !self.locked {
+            Some(format!("Landing radar lock at t={:.2}s.", lander.current_time))
+        } else if !in_range && self.locked {
+            Some(format!("\x1b[33mLanding radar lost lock at t={:.2}s.\x1b[0m", lander.current_time))
+        } else {
+            None
+        };
+        self.locked = in_range;
+        if self.locked {
+            let altitude = height + (vehicle.radar_noise * height + 1.0) * self.rng.gen_normal();
+            let v_down = lander.v_down + (vehicle.radar_noise * lander.v_down.abs() + 0.5) * self.rng.gen_normal();
+            self.reading = Some((altitude.max(0.0), v_down));
+            self.velocity = (lander.horiz_speed + (vehicle.radar_noise * lander.horiz_speed.abs() + 0.5) * self.rng.gen_normal(),
+                             lander.cross_speed + (vehicle.radar_noise * lander.cross_speed.abs() + 0.5) * self.rng.gen_normal());
+            self.fresh = true;
+        }
+        message
+    }
+}
+
+// Inertial measurement unit: accelerometers with a bias that drifts, a scale-factor error and noise
+struct Imu {
+    bias:  [f64; 3],  // ft/s^2 (up, downrange, crossrange)
+    scale: [f64; 3],  // fraction
+    rng:   SimpleRng,
+}
+
+impl Imu {
+    fn new(vehicle: &VehicleConfig, rng: &mut SimpleRng) -> Imu {
+        let mut rng = SimpleRng::with_seed(rng.next() as u64);
+        let bias = [0; 3].map(|_| vehicle.imu_bias * rng.gen_normal());
+        let scale = [0; 3].map(|_| vehicle.imu_scale * rng.gen_normal());
+        Imu { bias, scale, rng }
+    }
+
+    // Error in this step's measured acceleration on each axis
+    fn error(&mut self, specific_force: [f64; 3], vehicle: &VehicleConfig, dt: f64) -> [f64; 3] {
+        let mut error = [0.0; 3];
+        for axis in 0..3 {
+            self.bias[axis] += vehicle.imu_drift * dt.sqrt() * self.rng.gen_normal();
+            error[axis] = self.bias[axis] + self.scale[axis] * specific_force[axis] + vehicle.imu_noise * self.rng.gen_normal();
+        }
+        error
+    }
+}
+
+// Kalman filter for one axis: position and velocity with their covariance
+#[derive(Clone, Copy)]
+struct NavAxis {
+    pos: f64,
+    vel: f64,
+    p:   [[f64; 2]; 2],  // ft^2, ft^2/s, ft^2/s^2
+}
+
+impl NavAxis {
+    fn new(pos: f64, vel: f64, pos_sigma: f64, vel_sigma: f64) -> NavAxis {
+        NavAxis { pos, vel, p: [[pos_sigma * pos_sigma, 0.0], [0.0, vel_sigma * vel_sigma]] }
+    }
+
+    // Propagate with a measured acceleration held over dt; scale shrinks the position change
+    // (ground covered at altitude on a spherical Moon)
+    fn predict(&mut self, acc: f64, dt: f64, scale: f64, acc_variance: f64) {
+        self.pos += (self.vel * dt + 0.5 * acc * dt * dt) * scale;
+        self.vel += acc * dt;
+        let dt_pos = dt * scale;
+        let [[a, b], [_, d]] = self.p;
+        let a = a + 2.0 * b * dt_pos + d * dt_pos * dt_pos + acc_variance * dt_pos.powi(4) / 4.0;
+        let b = b + d * dt_pos + acc_variance * dt_pos.powi(3) / 2.0;
+        let d = d + acc_variance * dt * dt;
+        self.p = [[a, b], [b, d]];
+    }
+
+    // Blend in a measurement of the position (index 0) or the velocity (index 1)
+    fn update(&mut self, index: usize, measured: f64, variance: f64) {
+        let innovation = measured - [self.pos, self.vel][index];
+        let s = self.p[index][index] + variance;
+        let gain = [self.p[0][index] / s, self.p[1][index] / s];
+        self.pos += gain[0] * innovation;
+        self.vel += gain[1] * innovation;
+        let row = self.p[index];
+        for (i, gain) in gain.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                self.p[i][j] -= gain * value;
+            }
+        }
+    }
+}
+
+// Navigation (--nav): IMU propagation blended with landing radar updates. The display, Mission
+// Control and the autopilot use the estimate; gravity is assumed to be modelled perfectly.
+struct Navigation {
+    enabled: bool,
+    imu:     Imu,
+    axes:    [NavAxis; 3],  // altitude and climb rate, downrange, crossrange
+}
+
+impl Navigation {
+    fn new(enabled: bool, lander: &LanderState, vehicle: &VehicleConfig, rng: &mut SimpleRng) -> Navigation {
+        let imu = Imu::new(vehicle, rng);
+        let (pos_sigma, vel_sigma) = (vehicle.nav_position_error, vehicle.nav_speed_error);
+        let mut axis = |pos: f64, vel: f64| {
+            NavAxis::new(pos + pos_sigma * rng.gen_normal(), vel + vel_sigma * rng.gen_normal(), pos_sigma, vel_sigma)
+        };
+        let axes = [axis(lander.altitude, -lander.v_down), axis(lander.horiz_pos, lander.horiz_speed),
+                    axis(lander.cross_pos, lander.cross_speed)];
+        Navigation { enabled, imu, axes }
+    }
+
+    // Carry the estimate across one step using the IMU's view of the acceleration
+    fn propagate(&mut self, before: &LanderState, after: &LanderState, vehicle: &VehicleConfig, spherical: bool, dt: f64) {
+        if !self.enabled {
+            return;
+        }
+        let acceleration = [(before.v_down - after.v_down) / dt, (after.horiz_speed - before.horiz_speed) / dt,
+                            (after.cross_speed - before.cross_speed) / dt];
+        let error = self.imu.error(after.specific_force, vehicle, dt);
+        let scale = if spherical { MOON_RADIUS / (MOON_RADIUS + self.axes[0].pos) } else { 1.0 };
+        let acc_variance = (vehicle.imu_noise + vehicle.imu_bias).powi(2);
+        for (axis, nav) in self.axes.iter_mut().enumerate() {
+            nav.predict(acceleration[axis] + error[axis], dt, if axis == 0 { 1.0 } else { scale }, acc_variance);
+        }
+    }
+
+    // Blend in a fresh landing radar measurement
+    fn radar_update(&mut self, radar: &LandingRadar, vehicle: &VehicleConfig, terrain: &Terrain) {
+        if !self.enabled || !radar.fresh {
+            return;
+        }
+        let Some((height, v_down)) = radar.reading else {
+            return;
+        };
+        let variance = |value: f64, floor: f64| (vehicle.radar_noise * value.abs() + floor).powi(2);
+        // The radar sees the terrain under the LEM, which the filter looks up at its own position
+        let elevation = terrain.elevation(self.axes[1].pos, self.axes[2].pos);
+        self.axes[0].update(0, height + elevation, variance(height, 1.0));
+        self.axes[0].update(1, -v_down, variance(v_down, 0.5));
+        self.axes[1].update(1, radar.velocity.0, variance(radar.velocity.0, 0.5));
+        self.axes[2].update(1, radar.velocity.1, variance(radar.velocity.1, 0.5));
+    }
+
+    // The state as the LEM's computer believes it to be (the true state without --nav)
+    fn estimate(&self, lander: &LanderState) -> LanderState {
+        let mut estimate = *lander;
+        if self.enabled {
+            estimate.altitude = self.axes[0].pos;
+            estimate.v_down = -self.axes[0].vel;
+            estimate.horiz_pos = self.axes[1].pos;
+            estimate.horiz_speed = self.axes[1].vel;
+            estimate.cross_pos = self.axes[2].pos;
+            estimate.cross_speed = self.axes[2].vel;
+        }
+        estimate
+    }
+}
+
+// Lunar dust (--dust): below 100 ft the exhaust blows a sheet of dust across the surface,
+// so the horizontal speed and position judged from the window lose precision
+const DUST_ALTITUDE: f64 = 100.0;       // ft (height above the terrain where dust starts to blow)
+const DUST_SPEED_ERROR: f64 = 3.0;      // ft/s (horizontal speed error with the surface hidden)
+const DUST_POSITION_ERROR: f64 = 50.0;  // ft (position error with the surface hidden)
+
+struct DustModel {
+    enabled:   bool,
+    announced: bool,       // the crew has reported the dust
+    rng:       SimpleRng,
+}
+
+impl DustModel {
+    fn new(enabled: bool, rng: &mut SimpleRng) -> DustModel {
+        DustModel { enabled, announced: false, rng: SimpleRng::with_seed(rng.next() as u64) }
+    }
+
+    // From 0 (clear view) to 1 (surface hidden): thicker lower down and at higher thrust
+    fn obscuration(&self, lander: &LanderState, terrain: &Terrain) -> f64 {
+        if !self.enabled {
+            return 0.0;
+        }
+        let height = ((DUST_ALTITUDE - height_above_terrain(lander, terrain)) / DUST_ALTITUDE).clamp(0.0, 1.0);
+        let thrust = (lander.dps_level / hover_burn_rate(lander.mass)).min(1.0);
+        height * thrust
+    }
+
+    // Returns a message the first time the dust gets thick enough to notice
+    fn update(&mut self, lander: &LanderState, terrain: &Terrain) -> Option<String> {
+        if self.announced || self.obscuration(lander, terrain) < 0.2 {
+            return None;
+        }
+        self.announced = true;
+        Some(format!("\x1b[33mPicking up some dust at t={:.2}s: horizontal speed and position are hard to judge. Use the instruments.\x1b[0m",
+            lander.current_time))
+    }
+
+    fn degrade(&mut self, shown: &mut LanderState, obscuration: f64) {
+        if obscuration <= 0.0 {
+            return;
+        }
+        shown.horiz_speed += obscuration * DUST_SPEED_ERROR * self.rng.gen_normal();
+        shown.cross_speed += obscuration * DUST_SPEED_ERROR * self.rng.gen_normal();
+        shown.horiz_pos += obscuration * DUST_POSITION_ERROR * self.rng.gen_normal();
+        shown.cross_pos += obscuration * DUST_POSITION_ERROR * self.rng.gen_normal();
+    }
+}
+
+// Everything the crew reads the LEM's state from
+struct Instruments {
+    gauge: FuelGauge,
+    radar: LandingRadar,
+    nav:   Navigation,
+    dust:  DustModel,
+}
+
+// Failures that can be injected at random (--random-failures) or by the instructor (--fail)
+#[derive(Clone, Copy, PartialEq)]
+enum FailureKind {
+    ThrustLoss,     // DPS delivers only part of the commanded thrust
+    StuckThrottle,  // throttle frozen at its setting (engine stop still works)
+    RadarFailure,   // landing radar gives no more data
+    RcsJet,         // one RCS jet fails off
+    CommLoss,       // no contact with Mission Control for a while
+}
+
+const FAILURE_KINDS: [FailureKind; 5] = [
+    FailureKind::ThrustLoss, FailureKind::StuckThrottle, FailureKind::RadarFailure, FailureKind::RcsJet, FailureKind::CommLoss,
+];
+const FAILURE_THRUST_FACTOR: f64 = 0.7;  // fraction of the commanded thrust after a thrust loss
+const COMM_LOSS_DURATION: f64 = 30.0;    // s
+
+impl FailureKind {
+    fn name(&self) -> &'static str {
+        match self {
+            FailureKind::ThrustLoss => "thrust",
+            FailureKind::StuckThrottle => "throttle",
+            FailureKind::RadarFailure => "radar",
+            FailureKind::RcsJet => "rcs",
+            FailureKind::CommLoss => "comm",
+        }
+    }
+
+    fn parse(name: &str) -> Option<FailureKind> {
+        FAILURE_KINDS.iter().copied().find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+// When an instructor-scripted failure happens
+#[derive(Clone, Copy)]
+enum FailureTrigger {
+    Time(f64),      // s
+    Altitude(f64),  // ft (height above the terrain, on the way down)
+}
+
+#[derive(Clone, Copy)]
+struct ScriptedFailure {
+    kind:    FailureKind,
+    trigger: FailureTrigger,
+}
+
+// Failure injection: decides when failures happen and holds their lasting effects
+struct FailureInjector {
+    rates:          [f64; 5],               // chance per minute of flight, indexed like FAILURE_KINDS
+    scripted:       Vec<ScriptedFailure>,   // not yet fired
+    occurred:       [bool; 5],
+    thrust_factor:  f64,                    // fraction of the commanded thrust delivered
+    stuck_throttle: Option<f64>,            // lbs/s (vBurn the throttle is frozen at)
+    last_v_burn:    f64,                    // lbs/s (last nonzero vBurn commanded)
+    comm_until:     Option<f64>,            // s (contact with Mission Control returns)
+    rng:            SimpleRng,
+}
+
+impl FailureInjector {
+    fn new(rates: [f64; 5], scripted: Vec<ScriptedFailure>, rng: &mut SimpleRng) -> FailureInjector {
+        FailureInjector {
+            rates, scripted, occurred: [false; 5], thrust_factor: 1.0, stuck_throttle: None, last_v_burn: 0.0,
+            comm_until: None,
+            rng: SimpleRng::with_seed(rng.next() as u64),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.scripted.is_empty() || self.rates.iter().any(|&rate| rate > 0.0)
+    }
+
+    fn comm_lost(&self) -> bool {
+        self.comm_until.is_some()
+    }
+
+    // vBurn the DPS actually gets; shutting the engine down still works with a stuck throttle
+    fn throttle(&mut self, v_burn: f64) -> f64 {
+        if v_burn <= 0.0 {
+            return 0.0;
+        }
+        self.last_v_burn = v_burn;
+        self.stuck_throttle.unwrap_or(v_burn) * self.thrust_factor
+    }
+
+    // Failures due after a step of dt seconds; returns the alarms raised
+    fn step(&mut self, lander: &mut LanderState, radar: &mut LandingRadar, terrain: &Terrain, dt: f64) -> Vec<String> {
+        let mut alarms = Vec::new();
+        if let Some(until) = self.comm_until {
+            if lander.current_time >= until {
+                self.comm_until = None;
+                alarms.push("Communications with Mission Control restored.".to_string());
+            }
+        }
+        let height = height_above_terrain(lander, terrain);
+        let mut due = Vec::new();
+        let last_v_burn = self.last_v_burn;
+        self.scripted.retain(|failure| {
+            let fire = match failure.trigger {
+                FailureTrigger::Time(time) => lander.current_time >= time,
+                FailureTrigger::Altitude(altitude) => height <= altitude,
+            } && (failure.kind != FailureKind::StuckThrottle || last_v_burn > 0.0);  // nothing to freeze yet
+            if fire {
+                due.push(failure.kind);
+            }
+            !fire
+        });
+        for (i, kind) in FAILURE_KINDS.iter().enumerate() {
+            // Random failures only strike systems that are in use and still working
+            let applies = match kind {
+                FailureKind::ThrustLoss | FailureKind::StuckThrottle => lander.dps_level > 0.0 && !self.occurred[i],
+                FailureKind::RadarFailure => radar.enabled && !radar.failed,
+                FailureKind::RcsJet => lander.rcs.jet_failed.iter().any(|failed| !failed),
+                FailureKind::CommLoss => !self.comm_lost(),
+            };
+            if applies && self.rng.gen_f64() < self.rates[i] * dt / 60.0 {
+                due.push(*kind);
+            }
+        }
+        for kind in due {
+            alarms.push(self.inject(kind, lander, radar));
+        }
+        alarms
+    }
+
+    fn inject(&mut self, kind: FailureKind, lander: &mut LanderState, radar: &mut LandingRadar) -> String {
+        self.occurred[FAILURE_KINDS.iter().position(|k| *k == kind).unwrap()] = true;
+        match kind {
+            FailureKind::ThrustLoss => {
+                self.thrust_factor = FAILURE_THRUST_FACTOR;
+                format!("[ALARM] DPS thrust loss: the engine delivers {:.0}% of the commanded thrust.", FAILURE_THRUST_FACTOR * 100.0)
+            }
+            FailureKind::StuckThrottle => {
+                // The throttle freezes at the last setting commanded, even if the engine is off right now
+                let stuck = self.last_v_burn;
+                self.stuck_throttle = Some(stuck);
+                format!("[ALARM] DPS throttle stuck at vBurn {:.1} lbs/s. Only an engine stop will change it.", stuck)
+            }
+            FailureKind::RadarFailure => {
+                radar.failed = true;
+                radar.locked = false;
+                "[ALARM] Landing radar failure: no altitude or velocity data.".to_string()
+            }
+            FailureKind::RcsJet => {
+                let working: Vec<usize> = (0..RCS_JETS.len()).filter(|&jet| !lander.rcs.jet_failed[jet]).collect();
+                if working.is_empty() {
+                    return "[ALARM] RCS jet failure: all jets have already failed.".to_string();
+                }
+                let jet = working[self.rng.next() as usize % working.len()];
+                lander.rcs.jet_failed[jet] = true;
+                let (quad, direction) = RCS_JETS[jet];
+                let direction = match direction {
+                    JetDirection::Up => "up",
+                    JetDirection::Down => "down",
+                    JetDirection::PlusX => "+X (downrange)",
+                    JetDirection::MinusX => "-X (uprange)",
+                    JetDirection::PlusY => "+Y (right)",
+                    JetDirection::MinusY => "-Y (left)",
+                };
+                format!("[ALARM] RCS jet failure: quad {} {} jet failed off.", quad, direction)
+            }
+            FailureKind::CommLoss => {
+                self.comm_until = Some(lander.current_time + COMM_LOSS_DURATION);
+                format!("[ALARM] Communications lost with Mission Control for about {:.0} s.", COMM_LOSS_DURATION)
+            }
+        }
+    }
+
+    // Failures still in effect, for "status"
+    fn describe(&self, lander: &LanderState, radar: &LandingRadar) -> Vec<String> {
+        let mut active = Vec::new();
+        if self.thrust_factor < 1.0 {
+            active.push(format!("DPS thrust {:.0}%", self.thrust_factor * 100.0));
+        }
+        if let Some(stuck) = self.stuck_throttle {
+            active.push(format!("throttle stuck at {:.1} lbs/s", stuck));
+        }
+        if radar.failed {
+            active.push("landing radar failed".to_string());
+        }
+        let failed_jets = lander.rcs.jet_failed.iter().filter(|failed| **failed).count();
+        if failed_jets > 0 {
+            active.push(format!("{} RCS jet(s) failed", failed_jets));
+        }
+        if let Some(until) = self.comm_until {
+            active.push(format!("no communications until t={:.0}s", until));
+        }
+        active
+    }
+}
+
+// 1201/1202 program alarms: with the rendezvous radar stealing cycles, the AGC executive runs out of
+// VAC areas (1201) or core sets (1202), restarts and drops guidance cycles (program-alarms scenario)
+const AGC_ALARM_RATE: f64 = 2.0;     // alarms per minute while the AGC flies
+const AGC_RESTART_TIME: f64 = 2.0;   // s (guidance commands frozen after a restart)
+const AGC_ALARM_WINDOW: f64 = 30.0;  // s
+const AGC_ALARM_LIMIT: usize = 3;    // alarms within the window that make Mission Control call NO-GO
+const AGC_SEGMENT: f64 = 10.0;       // s (flown by the AGC each time the pilot presses Enter)
+
+// Program alarm raised by the AGC and the call Mission Control makes on it
+#[derive(Clone, Copy)]
+struct ProgramAlarm {
+    code: u32,   // 1201 or 1202
+    go:   bool,  // GO to continue on the AGC, NO-GO to take over manually
+}
+
+struct AgcExecutive {
+    enabled:       bool,
+    in_control:    bool,      // the AGC flies the LEM; Enter at the prompt lets it continue
+    failed:        bool,      // NO-GO: the pilot flies manually from now on
+    alarms:        Vec<f64>,  // s (times of the program alarms)
+    restart_until: f64,       // s
+    rng:           SimpleRng,
+}
+
+impl AgcExecutive {
+    fn new(enabled: bool, rng: &mut SimpleRng) -> AgcExecutive {
+        AgcExecutive {
+            enabled, in_control: enabled, failed: false, alarms: Vec::new(), restart_until: 0.0,
+            rng: SimpleRng::with_seed(rng.next() as u64),
+        }
+    }
+
+    fn restarting(&self, time: f64) -> bool {
+        time < self.restart_until
+    }
+
+    // Executive overflow during a guidance step; Mission Control calls NO-GO when alarms come too often
+    fn step(&mut self, time: f64, dt: f64) -> Option<ProgramAlarm> {
+        if !self.enabled || self.failed || self.rng.gen_f64() >= AGC_ALARM_RATE * dt / 60.0 {
+            return None;
+        }
+        self.alarms.push(time);
+        self.restart_until = time + AGC_RESTART_TIME;
+        let code = if self.rng.gen_f64() < 0.7 { 1202 } else { 1201 };
+        let recent = self.alarms.iter().filter(|&&t| time - t < AGC_ALARM_WINDOW).count();
+        Some(ProgramAlarm { code, go: recent < AGC_ALARM_LIMIT })
+    }
+
+    fn describe(&self) -> String {
+        let state = if self.failed {
+            "down (NO-GO), manual control"
+        } else if self.in_control {
+            "in control"
+        } else {
+            "monitoring, manual control"
+        };
+        format!("AGC: {}, {} program alarm(s)", state, self.alarms.len())
+    }
+}
+
+// Advance the LEM one time step with the given burn rates (shared by the flight loop and projections)
+fn step_lander(lander: &mut LanderState, vehicle: &VehicleConfig, moon: &Moon, v_burn: f64, h_burn: f64, c_burn: f64, dt: f64) {
+    let mut v_burn = v_burn;
+    if vehicle.dps_envelope {
+        // Ignition follows the command; the engine flames out when the descent propellant is gone
+        lander.dps_ignited = v_burn > 0.0 && lander.descent_fuel_mass > 0.0;
+        if !lander.dps_ignited {
+            v_burn = 0.0;
+        }
+    }
+    let v_burn = engine_response(lander, vehicle, v_burn, dt);
+    let fuel_used = v_burn * dt;
+    let fuel_used = if fuel_used > lander.descent_fuel_mass { lander.descent_fuel_mass } else { fuel_used };
+    lander.descent_fuel_mass -= fuel_used;
+
+    // Horizontal translation and pitch torque come from the RCS, limited by the working jets
+    let mut h_burn = h_burn.clamp(-lander.rcs.translation_capacity(JetDirection::MinusX, vehicle),
+                                  lander.rcs.translation_capacity(JetDirection::PlusX, vehicle));
+    let mut c_burn = c_burn.clamp(-lander.rcs.translation_capacity(JetDirection::MinusY, vehicle),
+                                  lander.rcs.translation_capacity(JetDirection::PlusY, vehicle));
+    let mut pitch_torque = rcs_pitch_torque(lander, vehicle);
+    let torque_flow = pitch_torque.abs() / vehicle.rcs_moment_arm / vehicle.rcs_isp;  // lbs/s (thrust / Isp)
+    let rcs_wanted = (h_burn.abs() + c_burn.abs() + torque_flow) * dt;
+    if rcs_wanted > lander.rcs.propellant {
+        // Whatever is left in the tanks is shared in proportion; after that the jets fall silent
+        let scale = lander.rcs.propellant / rcs_wanted;
+        h_burn *= scale;
+        c_burn *= scale;
+        pitch_torque *= scale;
+        lander.rcs.propellant = 0.0;
+    } else {
+        lander.rcs.propellant -= rcs_wanted;
+    }
+    lander.mass = DESCENT_DRY_MASS + lander.descent_fuel_mass + ASCENT_DRY_MASS + ASCENT_FUEL_MASS + lander.rcs.propellant;
+    let mass_slugs = lander.mass / STANDARD_GRAVITY;   // Earth's Standard Gravity (32.174) in ft/s²
+
+    // DPS thrust acts along the vehicle's thrust axis and is resolved through the pitch angle
+    let mass_flow_rate_vertical = v_burn / STANDARD_GRAVITY;  // slugs/s
+    let thrust_force_vertical = mass_flow_rate_vertical * VEX;  // lbs
+    let thrust_acc_dps = thrust_force_vertical / mass_slugs;  // ft/s² along the thrust axis
+    let (gravity_down, gravity_along, gravity_across) = moon.gravity_at(lander);
+    let mut net_acc_vertical = gravity_down - thrust_acc_dps * lander.pitch.cos();  // gravity downward
+
+    // Horizontal thrust (RCS translation jets) plus the horizontal component of the DPS
+    let rcs_vex = vehicle.rcs_isp * STANDARD_GRAVITY;  // ft/s
+    let mut thrust_acc_horizontal = h_burn / STANDARD_GRAVITY * rcs_vex / mass_slugs
+        + thrust_acc_dps * lander.pitch.sin() + gravity_along;  // ft/s²
+    let mut thrust_acc_cross = c_burn / STANDARD_GRAVITY * rcs_vex / mass_slugs + gravity_across;  // ft/s² (crossrange jets only)
+    // What an accelerometer senses: everything but gravity and the motion of the local frame
+    let mut specific_force = [thrust_acc_dps * lander.pitch.cos(), thrust_acc_horizontal - gravity_along,
+                              thrust_acc_cross - gravity_across];
+
+    // Polar coordinates on a spherical Moon: orbital speed relieves gravity (centrifugal term) and
+    // the local horizontal turns as the LEM moves, trading horizontal for vertical speed
+    let radius = MOON_RADIUS + lander.altitude;  // ft (from the Moon's center)
+    if moon.spherical {
+        net_acc_vertical -= (lander.horiz_speed.powi(2) + lander.cross_speed.powi(2)) / radius;
+        thrust_acc_horizontal += lander.v_down * lander.horiz_speed / radius;
+        thrust_acc_cross += lander.v_down * lander.cross_speed / radius;
+    }
+    let (start_pos, start_cross) = (lander.horiz_pos, lander.cross_pos);
+
+    // Pitch dynamics driven by RCS torque
+    let pitch_acc = pitch_torque / vehicle.pitch_inertia;  // rad/s²
+
+    if vehicle.slosh {
+        // The RCS jets push horizontally; resolve them into the LEM's frame (x across the thrust axis)
+        let rcs_acc = h_burn / STANDARD_GRAVITY * rcs_vex / mass_slugs;  // ft/s²
+        let axial = thrust_acc_dps + rcs_acc * lander.pitch.sin();
+        let lateral = (rcs_acc * lander.pitch.cos(), c_burn / STANDARD_GRAVITY * rcs_vex / mass_slugs);
+        let (slosh_x, slosh_y) = step_slosh(lander, vehicle, axial, lateral, pitch_acc, dt);
+        thrust_acc_horizontal += slosh_x * lander.pitch.cos();
+        net_acc_vertical += slosh_x * lander.pitch.sin();
+        thrust_acc_cross += slosh_y;
+        specific_force[0] -= slosh_x * lander.pitch.sin();
+        specific_force[1] += slosh_x * lander.pitch.cos();
+        specific_force[2] += slosh_y;
+    }
+    lander.specific_force = specific_force;
+
+    rk4_vertical(&mut lander.altitude, &mut lander.v_down, dt, net_acc_vertical);
+    rk4_horizontal(&mut lander.horiz_pos, &mut lander.horiz_speed, dt, thrust_acc_horizontal);
+    rk4_horizontal(&mut lander.cross_pos, &mut lander.cross_speed, dt, thrust_acc_cross);
+    rk4_attitude(&mut lander.pitch, &mut lander.pitch_rate, dt, pitch_acc);
+    if moon.spherical {
+        // Positions are measured along the surface, so speed at altitude covers less ground
+        lander.horiz_pos = start_pos + (lander.horiz_pos - start_pos) * MOON_RADIUS / radius;
+        lander.cross_pos = start_cross + (lander.cross_pos - start_cross) * MOON_RADIUS / radius;
+    }
+    lander.current_time += dt;
+}
+
+// Landing gear and terrain limits
+const GEAR_RADIUS: f64 = 15.0;        // ft (footpads sit on a circle about 31 ft across)
+const TIP_OVER_SLOPE: f64 = 12.0;     // deg (steepest slope the LEM is qualified to land on)
+const BOULDER_CLEARANCE: f64 = 1.5;   // ft (descent stage ground clearance)
+const PROBE_LENGTH: f64 = 67.0 / 12.0;  // ft (lunar surface sensing probes below the footpads)
+const STRUT_CRUSH_LOAD: f64 = 5500.0;   // lbf (per leg, honeycomb cartridge in the primary strut)
+const STRUT_STROKE: f64 = 32.0 / 12.0;  // ft (primary strut stroke available)
+const FOOTPAD_AREA: f64 = 7.47;         // ft^2 (37-inch footpad)
+const REGOLITH_STIFFNESS: f64 = 6000.0; // psf per ft of sinkage (bearing pressure grows with depth)
+const CG_HEIGHT: f64 = 12.0;            // ft (center of mass above the footpads)
+const TIP_EDGE: f64 = 10.96;            // ft (center to the line between two adjacent footpads)
+const GYRATION_RADIUS: f64 = 6.0;       // ft (about the center of mass)
+
+// Engine shutdown at contact light
+const ENGINE_STOP_ALTITUDE: f64 = 50.0;   // ft (highest height above the terrain "stop" is accepted)
+const ENGINE_STOP_PROMPT: f64 = 1.0;      // s (by-the-book engine stop after contact light)
+const NOZZLE_DAMAGE_TIME: f64 = 2.5;      // s (engine run after contact that damages the nozzle)
+const CREW_REACTION: f64 = 1.0;           // s (engine still firing at touchdown is stopped this much later)
+const ENGINE_OFF_LEVEL: f64 = 2.5;        // lbs/s (delivered vBurn below which the engine counts as off)
+const TERRAIN_SPAN: f64 = 30000.0;    // ft (procedural terrain is mapped this far before the site)
+
+struct Crater {
+    x:      f64,  // ft (downrange)
+    y:      f64,  // ft (crossrange)
+    radius: f64,  // ft (rim radius)
+    depth:  f64,  // ft (below the surrounding surface)
+}
+
+struct Boulder {
+    x:      f64,  // ft (downrange)
+    y:      f64,  // ft (crossrange)
+    radius: f64,  // ft
+    height: f64,  // ft
+}
+
+// Surface elevation around the landing site, relative to the site's reference level (altitude 0)
+struct Terrain {
+    slope_x:  f64,  // ft/ft (regional downrange slope)
+    slope_y:  f64,  // ft/ft (regional crossrange slope)
+    origin:   (f64, f64),  // ft (point where the regional slope has zero elevation)
+    craters:  Vec<Crater>,
+    boulders: Vec<Boulder>,
+    dem:      Option<DemTerrain>,  // elevation grid replacing the procedural surface
+}
+
+// Elevation grid loaded from an ESRI ASCII grid or a simple GeoTIFF (map units and elevations in meters)
+struct ElevationGrid {
+    ncols:     usize,
+    nrows:     usize,
+    x_origin:  f64,       // m (map x of the west edge)
+    y_top:     f64,       // m (map y of the north edge)
+    cell_size: f64,       // m
+    values:    Vec<f64>,  // m (row by row, starting with the north row)
+}
+
+const DEM_MAX_CELLS: usize = 25_000_000;  // largest grid loaded (5,000 x 5,000 cells)
+
+// Number of cells in a grid, refusing sizes too large to hold in memory
+fn dem_cell_count(ncols: usize, nrows: usize) -> Result<usize, String> {
+    ncols.checked_mul(nrows).filter(|&cells| cells <= DEM_MAX_CELLS)
+        .ok_or_else(|| format!("grid of {} x {} cells is larger than the {} cells supported", ncols, nrows, DEM_MAX_CELLS))
+}
+
+// Bundled data files are looked up from the working directory, then beside the executable
+// and in its parent directory (for binaries/)
+fn find_data_file(path: &str) -> String {
+    if std::path::Path::new(path).exists() {
+        return path.to_string();
+    }
+    if let Ok(exe) = env::current_exe() {
+        for dir in exe.ancestors().skip(1).take(2) {
+            let candidate = dir.join(path);
+            if candidate.exists() {
+                return candidate.to_string_lossy().into_owned();
+            }
+        }
+    }
+    path.to_string()
+}
+
+impl ElevationGrid {
+    fn load(path: &str) -> Result<ElevationGrid, String> {
+        let path = &find_data_file(path);
+        let bytes = fs::read(path).map_err(|error| format!("Cannot read {}: {}", path, error))?;
+        let lower = path.to_ascii_lowercase();
+        let grid = if lower.ends_with(".tif") || lower.ends_with(".tiff") {
+            ElevationGrid::parse_geotiff(&bytes)
+        } else {
+            ElevationGrid::parse_ascii_grid(&String::from_utf8_lossy(&bytes))
+        };
+        grid.map_err(|message| format!("{}: {}", path, message))
+    }
+
+    // ESRI ASCII grid: ncols, nrows, xllcorner/xllcenter, yllcorner/yllcenter, cellsize,
+    // optional NODATA_value, then the values row by row from the north
+    fn parse_ascii_grid(text: &str) -> Result<ElevationGrid, String> {
+        let mut tokens = text.split_whitespace().peekable();
+        let (mut ncols, mut nrows, mut cell_size, mut nodata) = (0usize, 0usize, 0.0, None);
+        let (mut x_ll, mut y_ll, mut centered) = (0.0, 0.0, false);
+        while let Some(key) = tokens.peek().filter(|token| token.starts_with(|c: char| c.is_ascii_alphabetic())) {
+            let key = key.to_ascii_lowercase();
+            tokens.next();
+            let value = tokens.next().ok_or_else(|| format!("missing value for {}", key))?;
+            let number = value.parse::<f64>().map_err(|_| format!("invalid {} '{}'", key, value))?;
+            match key.as_str() {
+                "ncols" => ncols = number as usize,
+                "nrows" => nrows = number as usize,
+                "xllcorner" => x_ll = number,
+                "yllcorner" => y_ll = number,
+                "xllcenter" => { x_ll = number; centered = true; }
+                "yllcenter" => { y_ll = number; centered = true; }
+                "cellsize" => cell_size = number,
+                "nodata_value" => nodata = Some(number),
+                _ => return Err(format!("unknown header '{}'", key)),
+            }
+        }
+        if ncols < 2 || nrows < 2 || cell_size <= 0.0 {
+            return Err("header needs ncols and nrows of at least 2 and a positive cellsize".to_string());
+        }
+        if centered {
+            x_ll -= 0.5 * cell_size;
+            y_ll -= 0.5 * cell_size;
+        }
+        let cells = dem_cell_count(ncols, nrows)?;
+        let mut values = Vec::with_capacity(cells);
+        for token in tokens {
+            values.push(token.parse::<f64>().map_err(|_| format!("invalid elevation '{}'", token))?);
+        }
+        if values.len() != cells {
+            return Err(format!("expected {} elevations, found {}", cells, values.len()));
+        }
+        let mut grid = ElevationGrid { ncols, nrows, x_origin: x_ll, y_top: y_ll + nrows as f64 * cell_size, cell_size, values };
+        grid.fill_nodata(nodata);
+        Ok(grid)
+    }
+
+    // GeoTIFF subset: one band, uncompressed strips of 16/32-bit integers or 32/64-bit floats,
+    // georeferenced with ModelPixelScale and ModelTiepoint
+    fn parse_geotiff(bytes: &[u8]) -> Result<ElevationGrid, String> {
+        let little = match bytes.get(0..4) {
+            Some([0x49, 0x49, 42, 0]) => true,
+            Some([0x4d, 0x4d, 0, 42]) => false,
+            _ => return Err("not a TIFF file".to_string()),
+        };
+        let read = |offset: usize, size: usize| -> Result<u64, String> {
+            let field = bytes.get(offset..offset + size).ok_or_else(|| "truncated TIFF".to_string())?;
+            let mut value = 0u64;
+            for i in 0..size {
+                let byte = if little { field[size - 1 - i] } else { field[i] };
+                value = (value << 8) | byte as u64;
+            }
+            Ok(value)
+        };
+        let type_size = |field_type: u64| match field_type { 3 => 2, 4 => 4, 12 => 8, 16 => 8, _ => 1 };
+
+        // Collect the tags of the first image directory as lists of numbers
+        let ifd = read(4, 4)? as usize;
+        let mut tags: Vec<(u64, Vec<f64>)> = Vec::new();
+        for entry in 0..read(ifd, 2)? as usize {
+            let base = ifd + 2 + entry * 12;
+            let (tag, field_type, count) = (read(base, 2)?, read(base + 2, 2)?, read(base + 4, 4)? as usize);
+            let size = type_size(field_type);
+            if size * count > bytes.len() {
+                return Err("truncated TIFF".to_string());
+            }
+            let start = if size * count <= 4 { base + 8 } else { read(base + 8, 4)? as usize };
+            let mut numbers = Vec::with_capacity(count);
+            for i in 0..count {
+                let raw = read(start + i * size, size)?;
+                numbers.push(if field_type == 12 { f64::from_bits(raw) } else { raw as f64 });
+            }
+            tags.push((tag, numbers));
+        }
+        let tag = |id: u64| tags.iter().find(|(tag, _)| *tag == id).map(|(_, numbers)| numbers.clone());
+        let single = |id: u64, default: Option<f64>| -> Result<f64, String> {
+            tag(id).and_then(|numbers| numbers.first().copied()).or(default).ok_or_else(|| format!("missing TIFF tag {}", id))
+        };
+
+        let ncols = single(256, None)? as usize;
+        let nrows = single(257, None)? as usize;
+        let bits = single(258, Some(16.0))? as usize;
+        let format = single(339, Some(1.0))? as u64;  // 1 unsigned, 2 signed, 3 float
+        if single(259, Some(1.0))? != 1.0 {
+            return Err("compressed GeoTIFFs are not supported".to_string());
+        }
+        if single(277, Some(1.0))? != 1.0 {
+            return Err("only single-band GeoTIFFs are supported".to_string());
+        }
+        let scale = tag(33550).ok_or_else(|| "missing ModelPixelScale (no georeferencing)".to_string())?;
+        let tiepoint = tag(33922).ok_or_else(|| "missing ModelTiepoint (no georeferencing)".to_string())?;
+        if scale.len() < 2 || tiepoint.len() < 6 || scale[0] <= 0.0 || (scale[0] - scale[1]).abs() > 1e-9 * scale[0] {
+            return Err("georeferencing needs square pixels".to_string());
+        }
+        let offsets = tag(273).ok_or_else(|| "missing StripOffsets".to_string())?;
+        let rows_per_strip = single(278, Some(nrows as f64))? as usize;
+        if ncols == 0 || nrows == 0 {
+            return Err("image has no pixels".to_string());
+        }
+        if rows_per_strip == 0 {
+            return Err("invalid RowsPerStrip 0".to_string());
+        }
+        if ![16, 32, 64].contains(&bits) {
+            return Err(format!("unsupported {} bits per sample", bits));
+        }
+
+        let sample_size = bits / 8;
+        let mut values = Vec::with_capacity(dem_cell_count(ncols, nrows)?);
+        for row in 0..nrows {
+            let strip = offsets.get(row / rows_per_strip).ok_or_else(|| "too few strips".to_string())?;
+            let row_start = *strip as usize + (row % rows_per_strip) * ncols * sample_size;
+            for col in 0..ncols {
+                let raw = read(row_start + col * sample_size, sample_size)?;
+                values.push(match (format, bits) {
+                    (3, 32) => f32::from_bits(raw as u32) as f64,
+                    (3, 64) => f64::from_bits(raw),
+                    (2, 16) => raw as u16 as i16 as f64,
+                    (2, 32) => raw as u32 as i32 as f64,
+                    (1, 16) | (1, 32) => raw as f64,
+                    _ => return Err(format!("unsupported sample format {} with {} bits", format, bits)),
+                });
+            }
+        }
+        let nodata = tag(42113).and_then(|chars| {  // GDAL_NODATA, stored as ASCII text
+            chars.iter().map(|c| *c as u8 as char).filter(|c| *c != '\0').collect::<String>().trim().parse::<f64>().ok()
+        });
+        let mut grid = ElevationGrid {
+            ncols,
+            nrows,
+            x_origin: tiepoint[3] - tiepoint[0] * scale[0],
+            y_top: tiepoint[4] + tiepoint[1] * scale[1],
+            cell_size: scale[0],
+            values,
+        };
+        grid.fill_nodata(nodata);
+        Ok(grid)
+    }
+
+    // Replace missing cells with the mean of the valid ones
+    fn fill_nodata(&mut self, nodata: Option<f64>) {
+        let Some(nodata) = nodata else { return };
+        let valid: Vec<f64> = self.values.iter().copied().filter(|value| *value != nodata).collect();
+        let mean = if valid.is_empty() { 0.0 } else { valid.iter().sum::<f64>() / valid.len() as f64 };
+        for value in self.values.iter_mut().filter(|value| **value == nodata) {
+            *value = mean;
+        }
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.x_origin + 0.5 * self.ncols as f64 * self.cell_size, self.y_top - 0.5 * self.nrows as f64 * self.cell_size)
+    }
+
+    // Bilinear elevation (m) at map coordinates; points off the grid take the nearest edge value
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let col = ((x - self.x_origin) / self.cell_size - 0.5).clamp(0.0, (self.ncols - 1) as f64);
+        let row = ((self.y_top - y) / self.cell_size - 0.5).clamp(0.0, (self.nrows - 1) as f64);
+        let (c0, r0) = ((col as usize).min(self.ncols - 2), (row as usize).min(self.nrows - 2));
+        let (fc, fr) = (col - c0 as f64, row - r0 as f64);
+        let at = |r: usize, c: usize| self.values[r * self.ncols + c];
+        let top = at(r0, c0) * (1.0 - fc) + at(r0, c0 + 1) * fc;
+        let bottom = at(r0 + 1, c0) * (1.0 - fc) + at(r0 + 1, c0 + 1) * fc;
+        top * (1.0 - fr) + bottom * fr
+    }
+}
+
+// An elevation grid placed under the flight path: the landing site sits at a map point and
+// downrange points along a compass heading
+struct DemTerrain {
+    grid:           ElevationGrid,
+    site:           (f64, f64),  // m (map coordinates of the landing site)
+    heading:        f64,         // rad (compass direction of downrange; map y is north)
+    site_elevation: f64,         // m
+    target:         (f64, f64),  // ft (downrange and crossrange position of the landing site)
+}
+
+impl DemTerrain {
+    // Surface elevation (ft) relative to the landing site
+    fn elevation(&self, x: f64, y: f64) -> f64 {
+        let downrange = (x - self.target.0) / FEET_PER_METER;
+        let crossrange = (y - self.target.1) / FEET_PER_METER;  // positive to the right of the track
+        let map_x = self.site.0 + downrange * self.heading.sin() + crossrange * self.heading.cos();
+        let map_y = self.site.1 + downrange * self.heading.cos() - crossrange * self.heading.sin();
+        (self.grid.sample(map_x, map_y) - self.site_elevation) * FEET_PER_METER
+    }
+}
+
+impl Terrain {
+    // The classic game's flat plane at altitude 0
+    fn flat() -> Terrain {
+        Terrain { slope_x: 0.0, slope_y: 0.0, origin: (0.0, 0.0), craters: Vec::new(), boulders: Vec::new(), dem: None }
+    }
+
+    // Surface taken from an elevation grid, with the landing site at map point site
+    fn from_dem(grid: ElevationGrid, site: (f64, f64), heading: f64, scenario: &Scenario) -> Terrain {
+        let site_elevation = grid.sample(site.0, site.1);
+        let dem = DemTerrain {
+            grid,
+            site,
+            heading: heading.to_radians(),
+            site_elevation,
+            target: (scenario.target_pos, scenario.target_cross),
+        };
+        Terrain { dem: Some(dem), ..Terrain::flat() }
+    }
+
+    // Craters, boulder fields and a gentle regional slope covering the approach to the site
+    fn procedural(rng: &mut SimpleRng, scenario: &Scenario) -> Terrain {
+        let (site_x, site_y) = (scenario.target_pos, scenario.target_cross);
+        let (x_min, x_max) = (site_x.min(0.0).max(site_x - TERRAIN_SPAN) - 2000.0, site_x + 4000.0);
+        let (y_min, y_max) = (site_y - 3000.0, site_y + 3000.0);
+        let slope = (rng.gen_f64() * 1.5).to_radians().tan();  // up to 1.5 degrees
+        let heading = rng.gen_f64() * std::f64::consts::TAU;
+        let mut terrain = Terrain {
+            slope_x: slope * heading.cos(),
+            slope_y: slope * heading.sin(),
+            origin: (site_x, site_y),
+            craters: Vec::new(),
+            boulders: Vec::new(),
+            dem: None,
+        };
+
+        // About one crater per 600,000 sq ft, mostly small ones
+        let count = (((x_max - x_min) * (y_max - y_min) / 600000.0) as usize).min(150);
+        for _ in 0..count {
+            let radius = 20.0 + 380.0 * rng.gen_f64().powi(3);
+            terrain.craters.push(Crater {
+                x: x_min + rng.gen_f64() * (x_max - x_min),
+                y: y_min + rng.gen_f64() * (y_max - y_min),
+                radius,
+                depth: 0.2 * radius,
+            });
+        }
+
+        // Boulder fields; the first lies a few hundred feet from the site
+        for field in 0..4 {
+            let (center_x, center_y) = if field == 0 {
+                let angle = rng.gen_f64() * std::f64::consts::TAU;
+                let distance = 300.0 + rng.gen_f64() * 500.0;
+                (site_x + distance * angle.cos(), site_y + distance * angle.sin())
+            } else {
+                (x_min + rng.gen_f64() * (x_max - x_min), y_min + rng.gen_f64() * (y_max - y_min))
+            };
+            for _ in 0..25 {
+                let angle = rng.gen_f64() * std::f64::consts::TAU;
+                let distance = 120.0 * rng.gen_f64().sqrt();
+                let radius = 1.0 + 4.0 * rng.gen_f64();
+                terrain.boulders.push(Boulder {
+                    x: center_x + distance * angle.cos(),
+                    y: center_y + distance * angle.sin(),
+                    radius,
+                    height: 0.8 * radius,
+                });
+            }
+        }
+        terrain
+    }
+
+    fn is_flat(&self) -> bool {
+        self.slope_x == 0.0 && self.slope_y == 0.0 && self.craters.is_empty() && self.boulders.is_empty() && self.dem.is_none()
+    }
+
+    // Surface elevation (ft) without boulders: regional slope plus crater bowls and rims
+    fn ground_elevation(&self, x: f64, y: f64) -> f64 {
+        if let Some(dem) = &self.dem {
+            return dem.elevation(x, y);
+        }
+        // The regional slope levels off beyond the mapped area (long approaches from orbit)
+        let mut elevation = self.slope_x * (x - self.origin.0).clamp(-TERRAIN_SPAN, TERRAIN_SPAN)
+            + self.slope_y * (y - self.origin.1).clamp(-TERRAIN_SPAN, TERRAIN_SPAN);
+        for crater in &self.craters {
+            let distance = (x - crater.x).hypot(y - crater.y);
+            let rim = 0.2 * crater.depth;
+            if distance < crater.radius {
+                elevation += crater.depth * ((distance / crater.radius).powi(2) - 1.0) + rim;  // bowl
+            } else if distance < 1.5 * crater.radius {
+                elevation += rim * (1.0 - (distance - crater.radius) / (0.5 * crater.radius)).powi(2);  // rim
+            }
+        }
+        elevation
+    }
+
+    // Height of the tallest boulder within radius of a point
+    fn tallest_boulder(&self, x: f64, y: f64, radius: f64) -> f64 {
+        self.boulders.iter()
+            .filter(|boulder| (x - boulder.x).hypot(y - boulder.y) < radius + boulder.radius)
+            .map(|boulder| boulder.height)
+            .fold(0.0, f64::max)
+    }
+
+    // Surface elevation (ft) including any boulder directly below the point
+    fn elevation(&self, x: f64, y: f64) -> f64 {
+        let mut elevation = self.ground_elevation(x, y);
+        for boulder in &self.boulders {
+            let distance = (x - boulder.x).hypot(y - boulder.y);
+            if distance < boulder.radius {
+                elevation += boulder.height * (1.0 - (distance / boulder.radius).powi(2)).sqrt();
+            }
+        }
+        elevation
+    }
+
+    // Rise per foot downrange and crossrange across the landing gear footprint
+    fn gradient(&self, x: f64, y: f64) -> (f64, f64) {
+        let dx = (self.ground_elevation(x + GEAR_RADIUS, y) - self.ground_elevation(x - GEAR_RADIUS, y)) / (2.0 * GEAR_RADIUS);
+        let dy = (self.ground_elevation(x, y + GEAR_RADIUS) - self.ground_elevation(x, y - GEAR_RADIUS)) / (2.0 * GEAR_RADIUS);
+        (dx, dy)
+    }
+
+    // Ground slope (deg) across the landing gear footprint
+    fn slope(&self, x: f64, y: f64) -> f64 {
+        let (dx, dy) = self.gradient(x, y);
+        dx.hypot(dy).atan().to_degrees()
+    }
+}
+
+// What the landing gear went through at touchdown
+struct TouchdownReport {
+    sinkage:     f64,   // ft (footpads pressed into the regolith)
+    stroke:      f64,   // ft (primary strut honeycomb crushed)
+    bottomed:    bool,  // stroke ran out
+    downhill:    f64,   // deg (slope along the direction the LEM tips; negative is uphill)
+    tip_energy:  f64,   // ft-lbf (rotational energy about the leading footpads)
+    tip_barrier: f64,   // ft-lbf (energy needed to lift the center of mass over them)
+    tipped:      bool,
+}
+
+// Footpads sink until the regolith pushes back as hard as the struts crush, then the
+// honeycomb strokes; whatever horizontal speed is left tries to rotate the LEM over its
+// leading footpads, helped by a downhill slope
+fn touchdown_dynamics(lander: &LanderState, terrain: &Terrain) -> TouchdownReport {
+    let mass_slugs = lander.mass / STANDARD_GRAVITY;
+    let leg_weight = mass_slugs * GRAVITY / 4.0;                    // lbf (on each leg at rest)
+    let leg_energy = 0.5 * mass_slugs / 4.0 * lander.v_down.powi(2);  // ft-lbf (each leg absorbs)
+    let soil = FOOTPAD_AREA * REGOLITH_STIFFNESS;                   // lbf per ft of sinkage
+    let crush_depth = STRUT_CRUSH_LOAD / soil;                      // ft (where the struts start to crush)
+    let soil_work = |depth: f64| 0.5 * soil * depth * depth - leg_weight * depth;
+    let (sinkage, stroke) = if soil_work(crush_depth) >= leg_energy {
+        ((leg_weight + (leg_weight * leg_weight + 2.0 * soil * leg_energy).sqrt()) / soil, 0.0)
+    } else {
+        (crush_depth, (leg_energy - soil_work(crush_depth)) / (STRUT_CRUSH_LOAD - leg_weight))
+    };
+
+    // Tip over the footpads in the direction of travel (or straight downhill when nearly stopped)
+    let speed = ground_speed(lander);
+    let (gx, gy) = terrain.gradient(lander.horiz_pos, lander.cross_pos);
+    let direction = if speed > 0.1 {
+        (lander.horiz_speed / speed, lander.cross_speed / speed)
+    } else if gx.hypot(gy) > 0.0 {
+        (-gx / gx.hypot(gy), -gy / gx.hypot(gy))
+    } else {
+        (1.0, 0.0)
+    };
+    let downhill = (-(gx * direction.0 + gy * direction.1)).atan();
+    let arm = CG_HEIGHT.hypot(TIP_EDGE);
+    let lean = (TIP_EDGE / CG_HEIGHT).atan() - downhill;  // rad (pivot to center of mass, from vertical)
+    let tip_energy = 0.5 * mass_slugs * speed * speed * CG_HEIGHT.powi(2) / (arm * arm + GYRATION_RADIUS.powi(2));
+    let tip_barrier = if lean > 0.0 { mass_slugs * GRAVITY * arm * (1.0 - lean.cos()) } else { 0.0 };
+    TouchdownReport {
+        sinkage: sinkage.max(leg_weight / soil),
+        stroke: stroke.min(STRUT_STROKE),
+        bottomed: stroke > STRUT_STROKE,
+        downhill: downhill.to_degrees(),
+        tip_energy,
+        tip_barrier,
+        tipped: tip_energy >= tip_barrier,
+    }
+}
+
+// Height of the LEM above the terrain directly below it
+fn height_above_terrain(lander: &LanderState, terrain: &Terrain) -> f64 {
+    lander.altitude - terrain.elevation(lander.horiz_pos, lander.cross_pos)  // ft
+}
+
+// Buried point mass producing a gravity anomaly (mascon)
+#[derive(Clone, Copy)]
+struct Mascon {
+    x:       f64,  // ft (downrange)
+    y:       f64,  // ft (crossrange)
+    depth:   f64,  // ft (below the reference level)
+    anomaly: f64,  // mGal (extra gravity at the surface directly above)
+}
+
+const FT_S2_PER_MGAL: f64 = 1.0e-5 * FEET_PER_METER;  // 1 mGal = 0.00001 m/s^2
+
+// Lunar gravity: the uniform value plus the pull of any mascons
+struct GravityField {
+    mascons: Vec<Mascon>,
+}
+
+impl GravityField {
+    // Downward, downrange and crossrange gravitational acceleration (ft/s^2) at the LEM
+    fn acceleration(&self, lander: &LanderState) -> (f64, f64, f64) {
+        let (mut down, mut along, mut across) = (GRAVITY, 0.0, 0.0);
+        for mascon in &self.mascons {
+            // GM chosen so the anomaly is reached at the surface directly above the mass
+            let gm = mascon.anomaly * FT_S2_PER_MGAL * mascon.depth * mascon.depth;
+            let (dx, dy, dz) = (mascon.x - lander.horiz_pos, mascon.y - lander.cross_pos, mascon.depth + lander.altitude);
+            let r = (dx * dx + dy * dy + dz * dz).sqrt();
+            let pull = gm / (r * r * r);
+            down += pull * dz;
+            along += pull * dx;
+            across += pull * dy;
+        }
+        (down, along, across)
+    }
+}
+
+// Everything about the Moon the LEM flies over
+struct Moon {
+    terrain:   Terrain,
+    gravity:   GravityField,
+    spherical: bool,  // inverse-square gravity and surface curvature (descent from orbit)
+}
+
+impl Moon {
+    // Gravity at the LEM; on a spherical Moon it falls off with the square of the distance from the center
+    fn gravity_at(&self, lander: &LanderState) -> (f64, f64, f64) {
+        let (mut down, along, across) = self.gravity.acceleration(lander);
+        if self.spherical {
+            down += GRAVITY * ((MOON_RADIUS / (MOON_RADIUS + lander.altitude)).powi(2) - 1.0);
+        }
+        (down, along, across)
+    }
+}
+
+// Result of holding a constant burn from a given state (used by Step 6 and "project")
+#[derive(Clone, Copy)]
+struct Projection {
+    elapsed:       f64,          // seconds simulated
+    state:         LanderState,  // projected state at the end
+    fuel_out_time: Option<f64>,  // seconds until the descent fuel ran out, if it did
+    landed:        bool,         // true if the surface was reached
+}
+
+// Simulate a constant burn until touchdown, max_time seconds, or the step limit
+fn project_burn(lander: &LanderState, vehicle: &VehicleConfig, moon: &Moon, v_burn: f64, h_burn: f64, c_burn: f64,
+                max_time: f64) -> Projection {
+    let mut projected = *lander;
+    let mut fuel_out_time = None;
+    let projection_dt = 0.1;  // Small time step for projection
+    let max_projection_steps = 10000;  // Limit iterations to prevent infinite loop
+    let max_steps = if max_time.is_finite() {
+        ((max_time / projection_dt) as i32).min(max_projection_steps)
+    } else {
+        max_projection_steps
+    };
+    let mut step_count = 0;
+
+    // Simulate descent until landing or limit reached
+    while height_above_terrain(&projected, &moon.terrain) > 0.0 && step_count < max_steps {
+        step_lander(&mut projected, vehicle, moon, v_burn, h_burn, c_burn, projection_dt);
+        step_count += 1;
+        if projected.descent_fuel_mass <= 0.0 && fuel_out_time.is_none() && v_burn > 0.0 {
+            fuel_out_time = Some(projected.current_time - lander.current_time);
+        }
+    }
+
+    Projection {
+        elapsed: projected.current_time - lander.current_time,
+        state: projected,
+        fuel_out_time,
+        landed: height_above_terrain(&projected, &moon.terrain) <= 0.0,
+    }
+}
+
+// A hypothetical burn requested with the "project" command
+#[derive(Clone, Copy)]
+struct WhatIf {
+    duration:    f64,
+    v_burn:      f64,
+    h_burn:      f64,
+    c_burn:      f64,
+    end_of_burn: Projection,  // state when the hypothetical burn ends
+    touchdown:   Projection,  // state at touchdown if the rates are then held
+}
+
+fn project_what_if(lander: &LanderState, vehicle: &VehicleConfig, moon: &Moon, duration: f64, v_burn: f64, h_burn: f64,
+                   c_burn: f64) -> WhatIf {
+    let end_of_burn = project_burn(lander, vehicle, moon, v_burn, h_burn, c_burn, duration);
+    let touchdown = if end_of_burn.landed {
+        end_of_burn
+    } else {
+        // Hold the same rates to touchdown, as Mission Control does in Step 6
+        let mut rest = project_burn(&end_of_burn.state, vehicle, moon, v_burn, h_burn, c_burn, f64::INFINITY);
+        rest.elapsed += end_of_burn.elapsed;
+        rest.fuel_out_time = end_of_burn.fuel_out_time.or(rest.fuel_out_time.map(|t| t + end_of_burn.elapsed));
+        rest
+    };
+    WhatIf { duration, v_burn, h_burn, c_burn, end_of_burn, touchdown }
+}
+
+// Print a what-if report; base_time is the flight time the projection started from
+fn report_what_if(what_if: &WhatIf, base_time: f64, prefix: &str) {
+    println!("\x1b[33m{} {:.1}s at vBurn={:.2} hBurn={:.2} cBurn={:.2} from t={:.2}s:\x1b[0m",
+        prefix, what_if.duration, what_if.v_burn, what_if.h_burn, what_if.c_burn, base_time);
+    let end = &what_if.end_of_burn.state;
+    println!("\x1b[33m    End of burn: t={:.2}s  Alt={:.3}  vDown={:.3}  hSpeed={:.3}  cSpeed={:.3}  Fuel={:.3}\x1b[0m",
+        base_time + what_if.end_of_burn.elapsed, end.altitude.max(0.0), end.v_down, end.horiz_speed, end.cross_speed,
+        end.descent_fuel_mass);
+    let touchdown = &what_if.touchdown;
+    if touchdown.landed {
+        println!("\x1b[33m    Touchdown (rates held): t={:.2}s  vDown={:.2} ft/s  hSpeed={:.2} ft/s  cSpeed={:.2} ft/s  Fuel={:.2} lbs\x1b[0m",
+            base_time + touchdown.elapsed, touchdown.state.v_down, touchdown.state.horiz_speed, touchdown.state.cross_speed,
+            touchdown.state.descent_fuel_mass);
+    } else {
+        println!("\x1b[33m    No touchdown within {:.0} s with these rates held.\x1b[0m", touchdown.elapsed);
+    }
+    if let Some(fuel_out) = touchdown.fuel_out_time {
+        let when = if touchdown.landed { ", before touchdown" } else { "" };
+        println!("\x1b[31m    WARNING: Descent fuel runs out at t={:.2}s{}.\x1b[0m", base_time + fuel_out, when);
+    }
+}
+
+fn print_state_line(lander: &LanderState, vehicle: &VehicleConfig, site: (f64, f64), terrain: &Terrain, instruments: &mut Instruments) {
+    // With navigation the crew sees the estimated state, not the true one
+    let mut shown = instruments.nav.estimate(lander);
+    let mut engine = if !vehicle.dps_envelope { "" } else if lander.dps_ignited { "  DPS=ON" } else { "  DPS=OFF" }.to_string();
+    if vehicle.throttle_tau > 0.0 {
+        engine += &format!("  Thrust={:.2}", lander.dps_level);
+    }
+    if !terrain.is_flat() {
+        engine += &format!("  AGL={:.1}  Slope={:.1}", height_above_terrain(&shown, terrain),
+            terrain.slope(shown.horiz_pos, shown.cross_pos));
+    }
+    // Blowing dust hides the surface, so horizontal motion is only judged roughly
+    let obscuration = instruments.dust.obscuration(lander, terrain);
+    instruments.dust.degrade(&mut shown, obscuration);
+    let decimals = if obscuration >= 0.5 { 0 } else if obscuration > 0.0 { 1 } else { 3 };
+    if obscuration > 0.0 {
+        engine += &format!("  Dust={:.0}%", obscuration * 100.0);
+    }
+    let (downrange, crossrange) = distance_to_go(&shown, site);
+    if vehicle.slosh {
+        engine += &format!("  Slosh={:.1}", lander.slosh.angle.0.to_degrees());
+    }
+    let gauge = &mut instruments.gauge;
+    if gauge.low_level {
+        engine += "  \x1b[31mLOW LEVEL\x1b[0m";
+    }
+    // With gauging the crew only has the indicated quantity, to the nearest pound
+    let fuel = gauge.read(lander, vehicle);
+    let fuel_text = if gauge.enabled { format!("{:.0}", fuel) } else { format!("{:.3}", fuel) };
+    // With the radar alone, altitude (above the terrain below) and vDown are its last measurement
+    let radar = &instruments.radar;
+    let (altitude, v_down) = match (radar.enabled && !instruments.nav.enabled, radar.reading) {
+        (false, _) => (format!("{:.3}", shown.altitude), format!("{:.3}", shown.v_down)),
+        (true, Some((altitude, v_down))) => (format!("{:.1}", altitude), format!("{:.1}", v_down)),
+        (true, None) => ("----".to_string(), "----".to_string()),
+    };
+    if radar.enabled {
+        engine += if radar.failed {
+            "  \x1b[31mRadar=FAIL\x1b[0m"
+        } else if radar.locked {
+            "  Radar=LOCK"
+        } else if radar.dropout > 0.0 {
+            "  Radar=DROPOUT"
+        } else {
+            "  Radar=NO LOCK"
+        };
+    }
+    println!("t={:.2}s  Alt={}  vDown={}  hPos={:.*}  hSpeed={:.*}  cPos={:.*}  cSpeed={:.*}  ToGo={:.0}  Fuel={}  RCS={:.1}  Pitch={:.1}  Hover={:.2}  T/Wmax={:.2}  dV={:.0}{}", 
+        lander.current_time, altitude, v_down, decimals, shown.horiz_pos, decimals, shown.horiz_speed, decimals, shown.cross_pos,
+        decimals, shown.cross_speed, downrange.hypot(crossrange), fuel_text,
+        lander.rcs.propellant, lander.pitch.to_degrees(), hover_burn_rate(lander.mass), thrust_to_weight(max_v_burn(vehicle), lander.mass),
+        descent_delta_v(lander.mass, fuel.min(lander.mass - 1.0)), engine);
+}
+
+// One recorder sample: the true state and the navigation estimate
+struct FlightSample {
+    time:     f64,       // s
+    truth:    [f64; 6],  // altitude, vDown, hPos, hSpeed, cPos, cSpeed
+    estimate: [f64; 6],
+    radar:    bool,      // landing radar locked
+}
+
+// Flight recorder: samples once a second and logs events, optionally written to a CSV file (--record)
+struct FlightRecorder {
+    samples: Vec<FlightSample>,
+    events:  Vec<(f64, String)>,  // s, flight log entry
+    file:    Option<BufWriter<fs::File>>,
+}
+
+impl FlightRecorder {
+    fn new(path: Option<&str>) -> Result<FlightRecorder, String> {
+        let file = match path {
+            Some(path) => {
+                let mut file = BufWriter::new(fs::File::create(path).map_err(|e| format!("Cannot create {}: {}", path, e))?);
+                writeln!(file, "t,alt,alt_est,v_down,v_down_est,horiz_pos,horiz_pos_est,horiz_speed,horiz_speed_est,\
+                    cross_pos,cross_pos_est,cross_speed,cross_speed_est,fuel,radar_lock,event")
+                    .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(FlightRecorder { samples: Vec::new(), events: Vec::new(), file })
+    }
+
+    fn sample(lander: &LanderState, instruments: &Instruments) -> FlightSample {
+        let state = |l: &LanderState| [l.altitude, l.v_down, l.horiz_pos, l.horiz_speed, l.cross_pos, l.cross_speed];
+        FlightSample {
+            time: lander.current_time,
+            truth: state(lander),
+            estimate: state(&instruments.nav.estimate(lander)),
+            radar: instruments.radar.locked,
+        }
+    }
+
+    fn write_row(&mut self, sample: &FlightSample, fuel: f64, event: &str) {
+        if let Some(file) = self.file.as_mut() {
+            let mut line = format!("{:.1}", sample.time);
+            for (truth, estimate) in sample.truth.iter().zip(sample.estimate.iter()) {
+                line += &format!(",{:.3},{:.3}", truth, estimate);
+            }
+            line += &format!(",{:.3},{},{}", fuel, sample.radar as i32, event.replace(',', ";"));
+            if writeln!(file, "{}", line).is_err() {
+                println!("\x1b[31mFlight recorder: write failed, recording stopped.\x1b[0m");
+                self.file = None;
+            }
+        }
+    }
+
+    fn record(&mut self, lander: &LanderState, instruments: &Instruments) {
+        let sample = FlightRecorder::sample(lander, instruments);
+        self.write_row(&sample, lander.descent_fuel_mass, "");
+        self.samples.push(sample);
+    }
+
+    // Flight log entry (alarms and failures), also written to the recording with the state at the time
+    fn log_event(&mut self, lander: &LanderState, instruments: &Instruments, event: &str) {
+        let sample = FlightRecorder::sample(lander, instruments);
+        self.write_row(&sample, lander.descent_fuel_mass, event);
+        self.events.push((lander.current_time, event.to_string()));
+    }
+
+    fn print_flight_log(&self) {
+        println!();
+        println!("Flight log:");
+        for (time, event) in &self.events {
+            println!("  t={:7.2}s  {}", time, event);
+        }
+    }
+
+    // Estimation error (estimate minus truth) over the flight
+    fn print_debrief(&self) {
+        if self.samples.is_empty() {
+            return;
+        }
+        println!();
+        println!("Navigation debrief (estimate minus truth):");
+        println!("       t       Alt    vDown     hPos   hSpeed     cPos   cSpeed  Radar");
+        let stride = self.samples.len().div_ceil(12);
+        let last = self.samples.len() - 1;
+        for (i, sample) in self.samples.iter().enumerate() {
+            if i % stride != 0 && i != last {
+                continue;
+            }
+            let mut row = format!("{:7.0}s", sample.time);
+            for (truth, estimate) in sample.truth.iter().zip(sample.estimate.iter()) {
+                row += &format!(" {:8.1}", estimate - truth);
+            }
+            println!("{}  {}", row, if sample.radar { "LOCK" } else { "-" });
+        }
+        let mut rms = [0.0; 6];
+        let mut largest = [0.0f64; 6];
+        for sample in &self.samples {
+            for axis in 0..6 {
+                let error = sample.estimate[axis] - sample.truth[axis];
+                rms[axis] += error * error;
+                largest[axis] = largest[axis].max(error.abs());
+            }
+        }
+        let rms = rms.map(|sum| (sum / self.samples.len() as f64).sqrt());
+        println!("     RMS {}", rms.iter().map(|e| format!("{:8.1}", e)).collect::<Vec<_>>().join(" "));
+        println!(" Largest {}", largest.iter().map(|e| format!("{:8.1}", e)).collect::<Vec<_>>().join(" "));
+    }
+}
+
+// What a Mission Control message says; landing_miss is the projected touchdown past and right of the site (ft)
+enum MessageKind {
+    Correction { v_burn_diff: f64, h_burn_diff: f64, c_burn_diff: f64, landing_miss: Option<(f64, f64)> },
+    Confirmation { landing_miss: Option<(f64, f64)> },  // burn rates are nominal
+    WhatIf(Box<WhatIf>),         // Answer to a pilot "project" request (Hard difficulty)
+    Callout(f64),                // Bingo callout: seconds of hover time left (--gauging)
+    ProgramAlarm(ProgramAlarm),  // GO/NO-GO call on an AGC program alarm
+}
+
+// A Mission Control message on its way to the crew
+struct Message {
+    eval_time:    f64,  // Time when state was evaluated (end of burn)
+    display_time: f64,  // Time to display the message (evalTime + delay)
+    kind:         MessageKind,
+}
+
+const MAX_PENDING_MESSAGES: usize = 100;
+
+// Queue a Mission Control message about the state at eval_time, heard delay seconds later.
+// Returns false (and drops the message) when the queue is full.
+fn send_message(pending: &mut Vec<Message>, eval_time: f64, delay: f64, kind: MessageKind) -> bool {
+    if pending.len() >= MAX_PENDING_MESSAGES {
+        return false;
+    }
+    pending.push(Message { eval_time, display_time: eval_time + delay, kind });
+    true
+}
+
+// Mission Control's report of where the held burn will touch down
+fn report_landing_miss(header: &str, landing_miss: Option<(f64, f64)>) {
+    if let Some((long, right)) = landing_miss {
+        if long.hypot(right) > 100.0 {
+            println!("\x1b[33m{} Projected touchdown {:.0} ft {} and {:.0} ft {} of the landing site\x1b[0m", header,
+                long.abs(), if long >= 0.0 { "long" } else { "short" },
+                right.abs(), if right >= 0.0 { "right" } else { "left" });
+        } else {
+            println!("\x1b[33m{} Projected touchdown is on the landing site\x1b[0m", header);
+        }
+    }
+}
+
+// Game difficulty, selected with --difficulty
+#[derive(Clone, Copy, PartialEq)]
+enum Difficulty {
+    Normal,
+    Hard,
+}
+
+// Starting conditions and target landing site of a mission, selected with --scenario
+struct Scenario {
+    name:           &'static str,
+    description:    &'static str,
+    event:          &'static str,  // what hands control to the pilot
+    altitude:       f64,           // ft
+    v_down:         (i32, i32),    // ft/s (random range, downward positive)
+    horiz_speed:    (i32, i32),    // ft/s (random range, downrange)
+    cross_speed:    (i32, i32),    // ft/s (random range, crossrange)
+    descent_fuel:   f64,           // lbs
+    target_pos:     f64,           // ft (downrange position of the landing site)
+    target_cross:   f64,           // ft (crossrange position of the landing site)
+    dem_file:       Option<&'static str>,  // bundled elevation tile
+    dem_site:       (f64, f64),    // m (map coordinates of the landing site in the tile)
+    dem_heading:    f64,           // deg (compass direction of downrange)
+    spherical:      bool,          // fly on a spherical Moon (descent from orbit)
+    agc_alarms:     bool,          // the AGC flies the approach and raises 1201/1202 program alarms
+}
+
+static SCENARIOS: [Scenario; 6] = [
+    Scenario {
+        name: "agc-failure",
+        description: "AGC fails at 7,500 ft after burn errors (the classic game)",
+        event: "AGC failed",
+        altitude: 7500.0,
+        v_down: (200, 700),
+        horiz_speed: (50, 200),
+        cross_speed: (0, 0),      // the classic game has no crossrange drift
+        descent_fuel: 1500.0,
+        target_pos: 8000.0,
+        target_cross: 0.0,
+        dem_file: None,
+        dem_site: (0.0, 0.0),
+        dem_heading: 270.0,
+        spherical: false,
+        agc_alarms: false,
+    },
+    Scenario {
+        name: "low-gate",
+        description: "Manual takeover at low gate, 500 ft above a site 1,600 ft ahead",
+        event: "Low gate, manual takeover",
+        altitude: 500.0,
+        v_down: (14, 20),
+        horiz_speed: (50, 70),
+        cross_speed: (-5, 5),
+        descent_fuel: 400.0,
+        target_pos: 1600.0,
+        target_cross: -100.0,
+        dem_file: None,
+        dem_site: (0.0, 0.0),
+        dem_heading: 270.0,
+        spherical: false,
+        agc_alarms: false,
+    },
+    Scenario {
+        name: "tranquility",
+        description: "Apollo 11 final approach over West crater to Tranquility Base",
+        event: "Manual takeover over the Sea of Tranquility",
+        altitude: 750.0,
+        v_down: (12, 20),
+        horiz_speed: (55, 75),
+        cross_speed: (-5, 5),
+        descent_fuel: 500.0,
+        target_pos: 2600.0,
+        target_cross: 0.0,
+        dem_file: Some("terrain/tranquility_base.asc"),
+        dem_site: (0.0, 0.0),
+        dem_heading: 270.0,
+        spherical: false,
+        agc_alarms: false,
+    },
+    Scenario {
+        name: "ocean-of-storms",
+        description: "Apollo 12 approach to the Surveyor crater site in the Ocean of Storms",
+        event: "Manual takeover over the Ocean of Storms",
+        altitude: 1000.0,
+        v_down: (15, 25),
+        horiz_speed: (60, 80),
+        cross_speed: (-5, 5),
+        descent_fuel: 600.0,
+        target_pos: 3000.0,
+        target_cross: 0.0,
+        dem_file: Some("terrain/ocean_of_storms.asc"),
+        dem_site: (0.0, 0.0),
+        dem_heading: 270.0,
+        spherical: false,
+        agc_alarms: false,
+    },
+    Scenario {
+        name: "pdi",
+        description: "Powered Descent Initiation at 50,000 ft, about 250 nautical miles from the landing site",
+        event: "Powered Descent Initiation",
+        altitude: 50000.0,
+        v_down: (-2, 2),
+        horiz_speed: (5550, 5570),
+        cross_speed: (-3, 3),
+        descent_fuel: 16000.0,
+        target_pos: 1_520_000.0,
+        target_cross: 0.0,
+        dem_file: None,
+        dem_site: (0.0, 0.0),
+        dem_heading: 270.0,
+        spherical: true,
+        agc_alarms: false,
+    },
+    Scenario {
+        name: "program-alarms",
+        description: "The AGC flies the approach from high gate while raising 1201/1202 program alarms",
+        event: "High gate, AGC in control",
+        altitude: 7000.0,
+        v_down: (115, 135),
+        horiz_speed: (490, 510),
+        cross_speed: (-5, 5),
+        descent_fuel: 3000.0,
+        target_pos: 26000.0,
+        target_cross: 0.0,
+        dem_file: None,
+        dem_site: (0.0, 0.0),
+        dem_heading: 270.0,
+        spherical: false,
+        agc_alarms: true,
+    },
+];
+
+fn find_scenario(name: &str) -> Option<&'static Scenario> {
+    SCENARIOS.iter().find(|scenario| scenario.name.eq_ignore_ascii_case(name))
+}
+
+// Downrange and crossrange distance still to fly to the designated landing site
+fn distance_to_go(lander: &LanderState, site: (f64, f64)) -> (f64, f64) {
+    (site.0 - lander.horiz_pos, site.1 - lander.cross_pos)  // ft
+}
+
+// Landing accuracy grade for the miss distance from the target site
+fn accuracy_grade(miss: f64) -> &'static str {
+    if miss <= 100.0 {
+        "Pinpoint landing"
+    } else if miss <= 500.0 {
+        "On target"
+    } else if miss <= 2000.0 {
+        "Acceptable (outside the planned landing area)"
+    } else {
+        "Missed the landing site"
+    }
+}
+
+// Landing Point Designator: each click moves the designated site this far
+const LPD_INCREMENT: f64 = 100.0;  // ft
+const LPD_MAX_CLICKS: f64 = 100.0;  // largest single redesignation (10,000 ft)
+
+// Hazard at a point of the terrain, as seen through the LPD
+#[derive(Clone, Copy)]
+enum Hazard {
+    Steep,     // slope beyond the tip-over limit
+    Boulders,  // boulder taller than the descent stage clearance
+    Marginal,  // slope over half the tip-over limit
+}
+
+impl Hazard {
+    fn describe(&self) -> &'static str {
+        match self {
+            Hazard::Steep => "slope too steep",
+            Hazard::Boulders => "boulders",
+            Hazard::Marginal => "marginal slope",
+        }
+    }
+}
+
+fn site_hazard(terrain: &Terrain, x: f64, y: f64) -> Option<Hazard> {
+    let slope = terrain.slope(x, y);
+    if slope > TIP_OVER_SLOPE {
+        Some(Hazard::Steep)
+    } else if terrain.tallest_boulder(x, y, GEAR_RADIUS) > BOULDER_CLEARANCE {
+        Some(Hazard::Boulders)
+    } else if slope > 0.5 * TIP_OVER_SLOPE {
+        Some(Hazard::Marginal)
+    } else {
+        None
+    }
+}
+
+// Map of the surface around the predicted landing point, downrange at the top.
+// P = predicted landing point, S = designated site, ^ = too steep, o = boulders, ~ = marginal slope
+fn print_lpd_map(terrain: &Terrain, center: (f64, f64), predicted: Option<(f64, f64)>, site: (f64, f64)) {
+    let spacing = 40.0;  // ft per cell
+    let half = 6;
+    let nearest = |point: (f64, f64), row: i32, col: i32| {
+        ((point.0 - center.0) / spacing).round() as i32 == row && ((point.1 - center.1) / spacing).round() as i32 == col
+    };
+    for row in (-half..=half).rev() {
+        let mut line = String::from("    ");
+        for col in -half..=half {
+            let (x, y) = (center.0 + row as f64 * spacing, center.1 + col as f64 * spacing);
+            let mark = if predicted.is_some_and(|point| nearest(point, row, col)) {
+                'P'
+            } else if nearest(site, row, col) {
+                'S'
+            } else {
+                match site_hazard(terrain, x, y) {
+                    Some(Hazard::Steep) => '^',
+                    Some(Hazard::Boulders) => 'o',
+                    Some(Hazard::Marginal) => '~',
+                    None => '.',
+                }
+            };
+            line.push(mark);
+            line.push(' ');
+        }
+        if row == half {
+            line += " downrange";
+        }
+        println!("{}", line);
+    }
+    println!("    (cells {:.0} ft apart, left to right across the track)", spacing);
+}
+
+// Horizontal delta-v and RCS propellant to move the touchdown point by an offset with t_go
+// seconds left: build up the extra speed, then take it out again before touchdown
+fn redesignation_cost(lander: &LanderState, vehicle: &VehicleConfig, offset: (f64, f64), t_go: f64) -> (f64, f64) {
+    let delta_v = 2.0 * offset.0.hypot(offset.1) / t_go.max(1.0);  // ft/s
+    let rcs_vex = vehicle.rcs_isp * STANDARD_GRAVITY;  // ft/s
+    (delta_v, lander.mass * (1.0 - (-delta_v / rcs_vex).exp()))  // lbs
+}
+
+// Braking phase guidance flies to high gate, where the autopilot hands over to the pilot
+const HIGH_GATE_ALTITUDE: f64 = 7000.0;  // ft
+const HIGH_GATE_RANGE: f64 = 26000.0;    // ft (short of the landing site)
+const HIGH_GATE_SPEED: f64 = 500.0;      // ft/s (horizontal)
+const HIGH_GATE_V_DOWN: f64 = 125.0;     // ft/s
+const AUTOPILOT_TIMEOUT: f64 = 900.0;    // seconds (default length of an "auto" segment)
+
+// Settings the autopilot flies for one time step
+struct AutopilotCommand {
+    v_burn: f64,  // lbs/s
+    c_burn: f64,  // lbs/s (crossrange RCS)
+    pitch:  f64,  // rad (thrust axis, the DPS does the downrange steering)
+    t_go:   f64,  // seconds to the guidance target
+}
+
+// Explicit guidance: acceleration now that reaches a point distance away at target_speed in t_go seconds
+fn guidance_acceleration(distance: f64, speed: f64, target_speed: f64, t_go: f64) -> f64 {
+    6.0 * (distance - speed * t_go) / (t_go * t_go) - 2.0 * (target_speed - speed) / t_go  // ft/s^2
+}
+
+// Braking phase: fly to high gate. Approach: fly down toward the designated site and, from 100 ft
+// above it, descend vertically at 3 ft/s with the horizontal speed nulled
+fn autopilot_command(lander: &LanderState, vehicle: &VehicleConfig, moon: &Moon, site: (f64, f64), braking: bool)
+                     -> AutopilotCommand {
+    let (gravity_down, gravity_along, gravity_across) = moon.gravity_at(lander);
+    let mut gravity_up = gravity_down;  // ft/s^2 the thrust has to hold up
+    let mut curvature = (0.0, 0.0);
+    if moon.spherical {
+        let radius = MOON_RADIUS + lander.altitude;
+        gravity_up -= (lander.horiz_speed.powi(2) + lander.cross_speed.powi(2)) / radius;
+        curvature = (lander.v_down * lander.horiz_speed / radius, lander.v_down * lander.cross_speed / radius);
+    }
+    let height = height_above_terrain(lander, &moon.terrain);
+    let (range, crossrange) = distance_to_go(lander, site);
+
+    // Wanted downrange, upward and crossrange accelerations (ft/s^2)
+    let (accel_along, accel_up, accel_cross, t_go) = if braking {
+        let range = site.0 - HIGH_GATE_RANGE - lander.horiz_pos;
+        // The real time to go is returned so the caller can end P63 at high gate; guidance itself
+        // never plans on less than 5 s
+        let t_go = 2.0 * range / (lander.horiz_speed + HIGH_GATE_SPEED);
+        let t_plan = t_go.max(5.0);
+        (guidance_acceleration(range, lander.horiz_speed, HIGH_GATE_SPEED, t_plan),
+         guidance_acceleration(HIGH_GATE_ALTITUDE - lander.altitude, -lander.v_down, -HIGH_GATE_V_DOWN, t_plan),
+         guidance_acceleration(crossrange, lander.cross_speed, 0.0, t_plan), t_go)
+    } else if height > 100.0 || range.hypot(crossrange) > 100.0 {
+        let drop = lander.altitude - moon.terrain.elevation(site.0, site.1) - 50.0;  // aim below 100 ft so it is crossed
+        let t_go = (2.0 * range.hypot(crossrange) / (ground_speed(lander) + 1.0)).max(5.0);
+        // The descent may finish first (then the LEM creeps along low over the site's level),
+        // but is never stretched out, which would let it sink below the aim point on the way
+        let t_go_down = (2.0 * drop / (lander.v_down + 5.0).max(1.0)).clamp(5.0, t_go);
+        (guidance_acceleration(range, lander.horiz_speed, 0.0, t_go),
+         guidance_acceleration(-drop, -lander.v_down, -5.0, t_go_down),
+         guidance_acceleration(crossrange, lander.cross_speed, 0.0, t_go), t_go)
+    } else {
+        let t_go = height / 3.0;
+        (-lander.horiz_speed / 3.0, (lander.v_down - 3.0) / 2.0, -lander.cross_speed / 3.0, t_go)
+    };
+
+    // Thrust acceleration that produces them
+    let thrust_up = accel_up + gravity_up;
+    let thrust_along = accel_along - gravity_along - curvature.0;
+    let thrust_cross = accel_cross - gravity_across - curvature.1;
+    let max_pitch = vehicle.max_pitch.to_radians();
+    let pitch = thrust_along.atan2(thrust_up).clamp(-max_pitch, max_pitch);
+    let mut v_burn = (thrust_along.hypot(thrust_up) * lander.mass / VEX).clamp(0.0, MAX_VERTICAL_BURN);
+    if vehicle.dps_envelope {
+        // Below the minimum throttle the DPS is pulsed; the gap between the restart and shutdown
+        // points keeps each pulse longer than the ignition delay
+        let min_rate = vehicle.dps_min_throttle * DPS_MAX_BURN;
+        let threshold = if lander.dps_ignited { 0.3 * min_rate } else { 0.7 * min_rate };
+        if v_burn < threshold {
+            v_burn = 0.0;
+        }
+    }
+    let rcs_vex = vehicle.rcs_isp * STANDARD_GRAVITY;  // ft/s
+    AutopilotCommand {
+        v_burn: clamp_to_envelope(v_burn, vehicle),
+        c_burn: (thrust_cross * lander.mass / rcs_vex).clamp(-MAX_HORIZ_BURN, MAX_HORIZ_BURN),
+        pitch,
+        t_go,
+    }
+}
+
+// Command-line options
+struct GameOptions {
+    difficulty:   Difficulty,
+    engine_model: bool,         // realistic DPS throttle envelope and ignition state
+    slosh:        bool,         // propellant slosh
+    gauging:      bool,         // indicated propellant quantity, low-level light and bingo callouts
+    radar:        bool,         // landing radar feeds the displayed altitude and vDown
+    radar_lock:   Option<(f64, f64)>,  // ft, ft/s (radar lock-on altitude and descent rate)
+    nav:          bool,         // IMU and navigation filter; the display shows the estimate
+    dust:         bool,         // blowing dust below 100 ft degrades horizontal speed and position
+    failure_rates: [f64; 5],    // random failure chance per minute, indexed like FAILURE_KINDS
+    scripted_failures: Vec<ScriptedFailure>,  // instructor failures at a time or altitude
+    record_file:  Option<String>,      // CSV flight recording of truth and estimate
+    throttle_tau: Option<f64>,  // s (throttle response time constant)
+    scenario:     &'static Scenario,
+    seed:         Option<u64>,  // fixed random seed (speeds and terrain)
+    terrain:      bool,         // procedural craters, boulders and slopes instead of a flat plane
+    dem_file:     Option<String>,      // elevation grid to fly over
+    dem_site:     Option<(f64, f64)>,  // m (map coordinates of the landing site)
+    dem_heading:  Option<f64>,         // deg (compass direction of downrange)
+    mascons:      Vec<Mascon>,
+}
+
+fn option_number(args: &[String], i: usize, name: &str) -> Result<f64, String> {
+    args.get(i)
+        .and_then(|a| a.parse::<f64>().ok())
+        .ok_or_else(|| format!("{} expects a number", name))
+}
+
+fn print_usage() {
+    println!("Usage: moonLander [options]");
+    println!("  --difficulty normal|hard        Hard routes \"project\" requests through Mission Control");
+    println!("  --engine-model                  DPS throttle envelope: 10-65% or 92.5-100%, minimum 10% once ignited");
+    println!("  --throttle-tau SECONDS          Throttle response time constant (0.3 with --engine-model, else 0)");
+    println!("  --slosh                         Propellant slosh: pushes the LEM around and disturbs the fuel gauge");
+    println!("  --gauging                       Indicated fuel with gauge error, low-level light and bingo callouts");
+    println!("  --radar                         Altitude and vDown from a landing radar with noise and dropouts");
+    println!("  --radar-lock ALT,RATE           Radar lock-on below ALT ft and RATE ft/s descent (default 40000,500)");
+    println!("  --nav                           IMU navigation blended with radar updates; shows the estimate (implies --radar)");
+    println!("  --dust                          Blowing dust below 100 ft blurs horizontal speed and position");
+    println!("  --fail KIND,t=SECONDS|alt=FEET  Scripted failure (thrust, throttle, radar, rcs, comm); repeatable");
+    println!("  --random-failures RATE|KIND=RATE,...");
+    println!("                                  Random failures, RATE = chance per minute of flight for each kind");
+    println!("  --record FILE                   Write truth and navigation estimate once a second to a CSV file");
+    println!("  --scenario NAME                 Starting conditions and landing site (default agc-failure):");
+    for scenario in SCENARIOS.iter() {
+        println!("      {:<14}              {}", scenario.name, scenario.description);
+    }
+    println!("  --seed N                        Random seed, to repeat the same speeds and terrain");
+    println!("  --terrain                       Procedural terrain with craters, boulders and slopes");
+    println!("  --dem FILE                      Elevation grid (.asc ESRI ASCII grid or .tif GeoTIFF, meters)");
+    println!("  --dem-site X,Y                  Map coordinates of the landing site (default grid center)");
+    println!("  --dem-heading DEG               Compass direction of the approach (default 270, westward)");
+    println!("  --mascon X,Y,DEPTH,MGAL         Gravity anomaly: point mass DEPTH ft below (X,Y) giving MGAL at the");
+    println!("                                  surface (repeat for more; real mascons are a few hundred mGal)");
+    println!("  -h, --help                      Show this help");
+}
+
+fn parse_options(args: &[String]) -> Result<GameOptions, String> {
+    let mut options = GameOptions {
+        difficulty: Difficulty::Normal, engine_model: false, slosh: false, gauging: false, throttle_tau: None, scenario: &SCENARIOS[0],
+        radar: false, radar_lock: None, nav: false, dust: false, record_file: None,
+        failure_rates: [0.0; 5], scripted_failures: Vec::new(),
+        seed: None, terrain: false, dem_file: None, dem_site: None, dem_heading: None,
+        mascons: Vec::new(),
+    };
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--difficulty" => {
+                i += 1;
+                options.difficulty = match args.get(i).map(|a| a.as_str()) {
+                    Some("normal") => Difficulty::Normal,
+                    Some("hard") => Difficulty::Hard,
+                    _ => return Err("--difficulty expects normal or hard".to_string()),
+                };
+            }
+            "--engine-model" => options.engine_model = true,
+            "--slosh" => options.slosh = true,
+            "--gauging" => options.gauging = true,
+            "--radar" => options.radar = true,
+            "--dust" => options.dust = true,
+            "--fail" => {
+                i += 1;
+                let usage = "--fail expects KIND,t=SECONDS or KIND,alt=FEET (KIND: thrust, throttle, radar, rcs, comm)";
+                let (name, trigger) = args.get(i).and_then(|a| a.split_once(',')).ok_or_else(|| usage.to_string())?;
+                let kind = FailureKind::parse(name).ok_or_else(|| usage.to_string())?;
+                let trigger = match trigger.split_once('=') {
+                    Some((key, value)) => match (key.trim(), value.trim().parse::<f64>()) {
+                        ("t", Ok(time)) if time >= 0.0 => FailureTrigger::Time(time),
+                        ("alt", Ok(altitude)) if altitude >= 0.0 => FailureTrigger::Altitude(altitude),
+                        _ => return Err(usage.to_string()),
+                    },
+                    None => return Err(usage.to_string()),
+                };
+                if kind == FailureKind::RadarFailure {
+                    options.radar = true;
+                }
+                options.scripted_failures.push(ScriptedFailure { kind, trigger });
+            }
+            "--random-failures" => {
+                i += 1;
+                let usage = "--random-failures expects RATE or KIND=RATE,... (chance per minute, KIND: thrust, throttle, radar, rcs, comm)";
+                let spec = args.get(i).ok_or_else(|| usage.to_string())?;
+                if let Ok(rate) = spec.trim().parse::<f64>() {
+                    if rate < 0.0 {
+                        return Err(usage.to_string());
+                    }
+                    options.failure_rates = [rate; 5];
+                } else {
+                    for field in spec.split(',') {
+                        let (name, rate) = field.split_once('=').ok_or_else(|| usage.to_string())?;
+                        let index = FailureKind::parse(name).and_then(|kind| FAILURE_KINDS.iter().position(|k| *k == kind));
+                        match (index, rate.trim().parse::<f64>()) {
+                            (Some(index), Ok(rate)) if rate >= 0.0 => options.failure_rates[index] = rate,
+                            _ => return Err(usage.to_string()),
+                        }
+                    }
+                }
+            }
+            "--nav" => {
+                options.nav = true;
+                options.radar = true;
+            }
+            "--record" => {
+                i += 1;
+                let path = args.get(i).ok_or_else(|| "--record expects a file name".to_string())?;
+                options.record_file = Some(path.clone());
+            }
+            "--radar-lock" => {
+                i += 1;
+                let lock = args.get(i).and_then(|a| a.split_once(','))
+                    .and_then(|(alt, rate)| Some((alt.trim().parse::<f64>().ok()?, rate.trim().parse::<f64>().ok()?)));
+                match lock {
+                    Some((alt, rate)) if alt > 0.0 && rate > 0.0 => {
+                        options.radar = true;
+                        options.radar_lock = Some((alt, rate));
+                    }
+                    _ => return Err("--radar-lock expects ALT,RATE (positive ft and ft/s)".to_string()),
+                }
+            }
+            "--throttle-tau" => {
+                i += 1;
+                let tau = option_number(args, i, "--throttle-tau")?;
+                if tau < 0.0 {
+                    return Err("--throttle-tau must not be negative".to_string());
+                }
+                options.throttle_tau = Some(tau);
+            }
+            "--scenario" => {
+                i += 1;
+                let name = args.get(i).ok_or_else(|| "--scenario expects a scenario name".to_string())?;
+                options.scenario = find_scenario(name).ok_or_else(|| format!("Unknown scenario '{}'", name))?;
+            }
+            "--seed" => {
+                i += 1;
+                let seed = args.get(i).and_then(|a| a.parse::<u64>().ok());
+                options.seed = Some(seed.ok_or_else(|| "--seed expects a whole number".to_string())?);
+            }
+            "--terrain" => options.terrain = true,
+            "--dem" => {
+                i += 1;
+                options.dem_file = Some(args.get(i).ok_or_else(|| "--dem expects a file name".to_string())?.clone());
+            }
+            "--dem-site" => {
+                i += 1;
+                let site = args.get(i).and_then(|a| a.split_once(','))
+                    .and_then(|(x, y)| Some((x.trim().parse::<f64>().ok()?, y.trim().parse::<f64>().ok()?)));
+                options.dem_site = Some(site.ok_or_else(|| "--dem-site expects X,Y map coordinates".to_string())?);
+            }
+            "--mascon" => {
+                i += 1;
+                let fields: Option<Vec<f64>> = args.get(i)
+                    .map(|a| a.split(',').map(|field| field.trim().parse::<f64>().ok()).collect())
+                    .unwrap_or(None);
+                match fields.as_deref() {
+                    Some(&[x, y, depth, anomaly]) if depth > 0.0 => options.mascons.push(Mascon { x, y, depth, anomaly }),
+                    _ => return Err("--mascon expects X,Y,DEPTH,MGAL (feet, feet, positive feet, mGal)".to_string()),
+                }
+            }
+            "--dem-heading" => {
+                i += 1;
+                options.dem_heading = Some(option_number(args, i, "--dem-heading")?);
+            }
+            other => return Err(format!("Unknown option '{}'", other)),
+        }
+        i += 1;
+    }
+    Ok(options)
+}
+
+// State checked by an "until" burn
+#[derive(Clone, Copy)]
+enum StateVariable {
+    Altitude,  // ft (above the landing site's elevation, not the terrain below)
+    VDown,     // ft/s (downward positive)
+    HSpeed,    // ft/s (total horizontal speed, downrange and crossrange)
+}
+
+#[derive(Clone, Copy)]
+enum Comparison {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+// Stop condition for an "until" burn, evaluated every small_dt step
+#[derive(Clone, Copy)]
+struct StopCondition {
+    variable:   StateVariable,
+    comparison: Comparison,
+    value:      f64,
+}
+
+impl StopCondition {
+    fn is_met(&self, lander: &LanderState) -> bool {
+        let current = match self.variable {
+            StateVariable::Altitude => lander.altitude,
+            StateVariable::VDown => lander.v_down,
+            StateVariable::HSpeed => ground_speed(lander),
+        };
+        match self.comparison {
+            Comparison::Less => current < self.value,
+            Comparison::LessEq => current <= self.value,
+            Comparison::Greater => current > self.value,
+            Comparison::GreaterEq => current >= self.value,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let name = match self.variable {
+            StateVariable::Altitude => "alt",
+            StateVariable::VDown => "vdown",
+            StateVariable::HSpeed => "hspeed",
+        };
+        let op = match self.comparison {
+            Comparison::Less => "<",
+            Comparison::LessEq => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterEq => ">=",
+        };
+        format!("{}{}{}", name, op, self.value)
+    }
+}
+
+fn parse_stop_condition(text: &str) -> Result<StopCondition, String> {
+    // Two-character operators are checked first so "<=" is not read as "<"
+    let operators = [("<=", Comparison::LessEq), (">=", Comparison::GreaterEq), ("<", Comparison::Less), (">", Comparison::Greater)];
+    for (symbol, comparison) in operators {
+        if let Some((name, value)) = text.split_once(symbol) {
+            let variable = match name.to_ascii_lowercase().as_str() {
+                "alt" | "altitude" => StateVariable::Altitude,
+                "vdown" => StateVariable::VDown,
+                "hspeed" => StateVariable::HSpeed,
+                _ => return Err(format!("Unknown state '{}'. Use alt, vdown or hspeed.", name)),
+            };
+            let value = value.parse::<f64>().ok().filter(|value| value.is_finite())
+                .ok_or_else(|| format!("Invalid value '{}' in condition.", value))?;
+            return Ok(StopCondition { variable, comparison, value });
+        }
+    }
+    Err(format!("Invalid condition '{}'. Example: vdown<=50", text))
+}
+
+const UNTIL_DEFAULT_TIMEOUT: f64 = 60.0;  // seconds (safety limit for "until" burns)
+
+// "until <condition> vburn=<rate> hburn=<rate> [cburn=<rate>] [timeout=<seconds>]"
+fn parse_until(parts: &[&str]) -> Result<BurnSegment, String> {
+    let usage = "Usage: until alt|vdown|hspeed<=value vburn=rate hburn=rate [cburn=rate] [timeout=seconds]";
+    let condition_text = parts.first().ok_or_else(|| usage.to_string())?;
+    let condition = parse_stop_condition(condition_text)?;
+    let mut segment = BurnSegment {
+        duration: UNTIL_DEFAULT_TIMEOUT, v_burn: 0.0, h_burn: 0.0, c_burn: 0.0, until: Some(condition), hover: false, autopilot: false,
+    };
+    for part in &parts[1..] {
+        let (key, value) = part.split_once('=').ok_or_else(|| format!("Expected key=value, found '{}'. {}", part, usage))?;
+        let value = value.parse::<f64>().ok().filter(|value| value.is_finite())
+            .ok_or_else(|| format!("Invalid number '{}' for {}.", value, key))?;
+        match key.to_ascii_lowercase().as_str() {
+            "vburn" => segment.v_burn = value,
+            "hburn" => segment.h_burn = value,
+            "cburn" => segment.c_burn = value,
+            "timeout" => segment.duration = value,
+            _ => return Err(format!("Unknown setting '{}'. {}", key, usage)),
+        }
+    }
+    if segment.duration <= 0.0 {
+        return Err("The timeout must be positive.".to_string());
+    }
+    Ok(segment)
+}
+
+// One burn of a pilot command or schedule
+#[derive(Clone, Copy)]
+struct BurnSegment {
+    duration: f64,                    // seconds (the safety timeout for "until" burns)
+    v_burn:   f64,                    // lbs/s
+    h_burn:   f64,                    // lbs/s (downrange)
+    c_burn:   f64,                    // lbs/s (crossrange)
+    until:    Option<StopCondition>,  // stop early once this condition is met
+    hover:    bool,                   // vBurn follows the hover rate as fuel burns ("hold")
+    autopilot: bool,                  // the autopilot sets vBurn, cBurn and pitch ("auto")
+}
+
+fn describe_segment(segment: &BurnSegment) -> String {
+    let lateral = if segment.c_burn != 0.0 { format!(" cBurn={:.2}", segment.c_burn) } else { String::new() };
+    if segment.autopilot {
+        return format!("auto {:.1}s (autopilot)", segment.duration);
+    }
+    if segment.hover {
+        return format!("hold {:.1}s vBurn=hover hBurn={:.2}{}", segment.duration, segment.h_burn, lateral);
+    }
+    match segment.until {
+        Some(condition) => format!("until {} vBurn={:.2} hBurn={:.2}{} (timeout {:.1}s)",
+            condition.describe(), segment.v_burn, segment.h_burn, lateral, segment.duration),
+        None => format!("{:.1}s vBurn={:.2} hBurn={:.2}{}", segment.duration, segment.v_burn, segment.h_burn, lateral),
+    }
+}
+
+// Relative ("+10", "-2") or absolute ("120", "hburn=-3") change to one field of the last burn
+#[derive(Clone, Copy)]
+enum Adjustment {
+    Relative(f64),
+    Absolute(f64),
+}
+
+impl Adjustment {
+    fn apply(&self, value: f64) -> f64 {
+        match *self {
+            Adjustment::Relative(delta) => value + delta,
+            Adjustment::Absolute(new_value) => new_value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct BurnAdjustment {
+    duration: Option<Adjustment>,
+    v_burn:   Option<Adjustment>,
+    h_burn:   Option<Adjustment>,
+    c_burn:   Option<Adjustment>,
+}
+
+// Commands accepted at the ">>" prompt
+enum PilotCommand {
+    Burn(BurnSegment),
+    Schedule(Vec<BurnSegment>),
+    Project { duration: f64, v_burn: f64, h_burn: f64, c_burn: f64 },
+    Abort,
+    Status,
+    Help,
+    Hold { duration: f64, h_burn: f64, c_burn: f64 },
+    Repeat,
+    Adjust(BurnAdjustment),
+    Pitch(f64),
+    Lpd(LpdAction),
+    Auto(f64),  // seconds
+    EngineStop,
+}
+
+// "lpd" shows the designator view; "lpd long|short|left|right [clicks]" moves the site
+#[derive(Clone, Copy)]
+enum LpdAction {
+    Show,
+    Move { long: f64, right: f64 },  // clicks
+    Reset,
+}
+
+fn print_command_help() {
+    println!("Commands:");
+    println!("  duration vBurn hBurn [cBurn] Burn for duration seconds (same as \"burn\")");
+    println!("  burn duration vBurn hBurn [cBurn]");
+    println!("                               vBurn 0 to {:.0}, hBurn and cBurn (crossrange) -{:.0} to {:.0} lbs/s",
+        MAX_VERTICAL_BURN, MAX_HORIZ_BURN, MAX_HORIZ_BURN);
+    println!("  d v h; d v h; ...            Fly a schedule of burns in order");
+    println!("  until vdown<=50 vburn=200 hburn=-3 [cburn=1] [timeout=60]");
+    println!("                               Burn until alt, vdown or hspeed reaches a value");
+    println!("  hold duration [hBurn [cBurn]]");
+    println!("                               Burn at the hover rate (vDown held constant)");
+    println!("  repeat                       Fly the last burn again");
+    println!("  vburn +10 | hburn -2 | cburn 1 | duration 5");
+    println!("                               Fly the last burn with a relative change (+/-) or new value");
+    println!("                               (use hburn=-3 to set a negative value)");
+    println!("  pitch degrees                Tilt the LEM so the descent engine thrusts partly sideways");
+    println!("                               (positive pushes toward +hPos; the RCS slews to the new attitude)");
+    println!("  project duration vBurn hBurn [cBurn]");
+    println!("                               Preview a burn without flying it");
+    println!("  auto [duration]              Engage the autopilot (braking phase to high gate, or approach to the site)");
+    println!("  lpd                          Landing Point Designator: predicted landing point and hazards");
+    println!("  lpd long|short|left|right [clicks]");
+    println!("                               Redesignate the landing site ({:.0} ft per click); \"lpd reset\" restores it",
+        LPD_INCREMENT);
+    println!("  (Enter)                      Let the AGC fly another {:.0} s while it is in control (program-alarms)", AGC_SEGMENT);
+    println!("  stop                         Engine stop: shut down the descent engine at contact light");
+    println!("  status                       Show the current state");
+    println!("  abort                        Abort to orbit using the ascent stage (same as -1 0 0)");
+    println!("  help                         Show this list");
 }
 
-// RK4 helper functions 
-fn compute_vertical_derivatives(state: [f64; 2], net_acc: f64) -> [f64; 2] {
-    let mut derivatives = [0.0; 2];
-    derivatives[0] = -state[1];  // d(altitude)/dt = -vDown
-    derivatives[1] = net_acc;    // d(vDown)/dt = netAcc
-    derivatives
+fn parse_field(name: &str, text: &str) -> Result<f64, String> {
+    text.parse::<f64>().ok().filter(|value| value.is_finite()).ok_or_else(|| format!("{} '{}' is not a number.", name, text))
 }
 
-fn compute_horizontal_derivatives(state: [f64; 2], horiz_acc: f64) -> [f64; 2] {
-    let mut derivatives = [0.0; 2];
-    derivatives[0] = state[1];   // d(horizPos)/dt = horizSpeed
-    derivatives[1] = horiz_acc;  // d(horizSpeed)/dt = horizAcc
-    derivatives
+fn check_burn_rates(v_burn: f64, h_burn: f64, c_burn: f64) -> Result<(), String> {
+    if !(0.0..=MAX_VERTICAL_BURN).contains(&v_burn) {
+        return Err(format!("vBurn {} is out of range. Allowed: 0 to {:.0} lbs/s.", v_burn, MAX_VERTICAL_BURN));
+    }
+    if !(-MAX_HORIZ_BURN..=MAX_HORIZ_BURN).contains(&h_burn) {
+        return Err(format!("hBurn {} is out of range. Allowed: -{:.0} to {:.0} lbs/s.", h_burn, MAX_HORIZ_BURN, MAX_HORIZ_BURN));
+    }
+    if !(-MAX_HORIZ_BURN..=MAX_HORIZ_BURN).contains(&c_burn) {
+        return Err(format!("cBurn {} is out of range. Allowed: -{:.0} to {:.0} lbs/s.", c_burn, MAX_HORIZ_BURN, MAX_HORIZ_BURN));
+    }
+    Ok(())
 }
 
-fn rk4_vertical(altitude: &mut f64, v_down: &mut f64, dt: f64, net_acc: f64) {
-    let state = [*altitude, *v_down];
-    let k1 = compute_vertical_derivatives(state, net_acc);
-    let temp_state = [state[0] + 0.5 * dt * k1[0], state[1] + 0.5 * dt * k1[1]];
-    let k2 = compute_vertical_derivatives(temp_state, net_acc);
-    let temp_state = [state[0] + 0.5 * dt * k2[0], state[1] + 0.5 * dt * k2[1]];
-    let k3 = compute_vertical_derivatives(temp_state, net_acc);
-    let temp_state = [state[0] + dt * k3[0], state[1] + dt * k3[1]];
-    let k4 = compute_vertical_derivatives(temp_state, net_acc);
+// "duration vBurn hBurn" with an optional crossrange "cBurn" (0 when omitted)
+fn parse_burn_triple(parts: &[&str]) -> Result<(f64, f64, f64, f64), String> {
+    if parts.len() != 3 && parts.len() != 4 {
+        return Err(format!("Expected duration, vBurn, hBurn and optional cBurn (three or four numbers), found {} value(s). Type \"help\" for commands.",
+            parts.len()));
+    }
+    let c_burn = match parts.get(3) {
+        Some(text) => parse_field("cBurn", text)?,
+        None => 0.0,
+    };
+    Ok((parse_field("duration", parts[0])?, parse_field("vBurn", parts[1])?, parse_field("hBurn", parts[2])?, c_burn))
+}
 
-    *altitude += (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]) * dt / 6.0;
-    *v_down += (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]) * dt / 6.0;
+// A schedule is several "duration vBurn hBurn" segments separated by semicolons
+fn parse_schedule(input: &str) -> Result<Vec<BurnSegment>, String> {
+    let mut segments = Vec::new();
+    for (index, text) in input.split(';').map(str::trim).filter(|t| !t.is_empty()).enumerate() {
+        let segment = parse_segment(text)
+            .map_err(|message| format!("Invalid input in segment {}. {}", index + 1, message))?;
+        if segment.duration <= 0.0 {
+            return Err(format!("Invalid segment {}. Duration must be positive (aborts cannot be scheduled).", index + 1));
+        }
+        check_burn_rates(segment.v_burn, segment.h_burn, segment.c_burn)
+            .map_err(|message| format!("Invalid segment {}. {}", index + 1, message))?;
+        segments.push(segment);
+    }
+    if segments.is_empty() {
+        return Err("Invalid input. The schedule has no segments.".to_string());
+    }
+    Ok(segments)
 }
 
-fn rk4_horizontal(horiz_pos: &mut f64, horiz_speed: &mut f64, dt: f64, horiz_acc: f64) {
-    let state = [*horiz_pos, *horiz_speed];
-    let k1 = compute_horizontal_derivatives(state, horiz_acc);
-    let temp_state = [state[0] + 0.5 * dt * k1[0], state[1] + 0.5 * dt * k1[1]];
-    let k2 = compute_horizontal_derivatives(temp_state, horiz_acc);
-    let temp_state = [state[0] + 0.5 * dt * k2[0], state[1] + 0.5 * dt * k2[1]];
-    let k3 = compute_horizontal_derivatives(temp_state, horiz_acc);
-    let temp_state = [state[0] + dt * k3[0], state[1] + dt * k3[1]];
-    let k4 = compute_horizontal_derivatives(temp_state, horiz_acc);
+// A segment is "duration vBurn hBurn [cBurn]", "burn duration vBurn hBurn [cBurn]" or an "until" burn
+fn parse_segment(text: &str) -> Result<BurnSegment, String> {
+    let mut parts: Vec<&str> = text.split_whitespace().collect();
+    if parts.first().is_some_and(|word| word.eq_ignore_ascii_case("until")) {
+        return parse_until(&parts[1..]);
+    }
+    if parts.first().is_some_and(|word| word.eq_ignore_ascii_case("burn")) {
+        parts.remove(0);
+    }
+    let (duration, v_burn, h_burn, c_burn) = parse_burn_triple(&parts)?;
+    Ok(BurnSegment { duration, v_burn, h_burn, c_burn, until: None, hover: false, autopilot: false })
+}
 
-    *horiz_pos += (k1[0] + 2.0 * k2[0] + 2.0 * k3[0] + k4[0]) * dt / 6.0;
-    *horiz_speed += (k1[1] + 2.0 * k2[1] + 2.0 * k3[1] + k4[1]) * dt / 6.0;
+// "vburn +10", "hburn=-3", "duration 5 vburn -20", ...
+fn parse_adjustment(parts: &[&str]) -> Result<BurnAdjustment, String> {
+    let mut adjustment = BurnAdjustment::default();
+    let mut i = 0;
+    while i < parts.len() {
+        let (key, value, explicit) = match parts[i].split_once('=') {
+            Some((key, value)) => (key, value, true),
+            None => {
+                i += 1;
+                let value = parts.get(i).ok_or_else(|| format!("Missing value after '{}'.", parts[i - 1]))?;
+                (parts[i - 1], *value, false)
+            }
+        };
+        let key = key.to_ascii_lowercase();
+        let name = match key.as_str() {
+            "duration" => "duration",
+            "vburn" => "vBurn",
+            "hburn" => "hBurn",
+            "cburn" => "cBurn",
+            _ => return Err(format!("Unknown field '{}'. Use duration, vburn, hburn or cburn.", key)),
+        };
+        let number = parse_field(name, value)?;
+        let change = if !explicit && (value.starts_with('+') || value.starts_with('-')) {
+            Adjustment::Relative(number)
+        } else {
+            Adjustment::Absolute(number)
+        };
+        match key.as_str() {
+            "duration" => adjustment.duration = Some(change),
+            "vburn" => adjustment.v_burn = Some(change),
+            "hburn" => adjustment.h_burn = Some(change),
+            _ => adjustment.c_burn = Some(change),
+        }
+        i += 1;
+    }
+    Ok(adjustment)
 }
 
-// Modified Correction struct with isConfirmation field
-#[derive(Clone, Copy)]
-struct Correction {
-    eval_time:       f64,  // Time when state was evaluated (end of burn)
-    display_time:    f64,  // Time to display correction (evalTime + 6.6)
-    v_burn_diff:     f64,
-    h_burn_diff:     f64,
-    is_confirmation: i32,  // 0 for correction, 1 for confirmation
+fn parse_command(input: &str) -> Result<PilotCommand, String> {
+    if input.contains(';') {
+        return parse_schedule(input).map(PilotCommand::Schedule);
+    }
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    let keyword = match parts.first() {
+        Some(word) => word.split('=').next().unwrap_or("").to_ascii_lowercase(),
+        None => return Err("Invalid input. Type \"help\" for a list of commands.".to_string()),
+    };
+    match keyword.as_str() {
+        "project" => match parse_burn_triple(&parts[1..]) {
+            Ok((duration, v_burn, h_burn, c_burn)) => Ok(PilotCommand::Project { duration, v_burn, h_burn, c_burn }),
+            Err(message) => Err(format!("Invalid input. {} Usage: project duration vBurn hBurn [cBurn]", message)),
+        },
+        "abort" => Ok(PilotCommand::Abort),
+        "stop" => Ok(PilotCommand::EngineStop),
+        "status" => Ok(PilotCommand::Status),
+        "help" | "?" => Ok(PilotCommand::Help),
+        "repeat" => Ok(PilotCommand::Repeat),
+        "hold" => {
+            let duration = parts.get(1).ok_or_else(|| "Invalid input. Usage: hold duration [hBurn [cBurn]]".to_string())?;
+            let duration = parse_field("duration", duration).map_err(|message| format!("Invalid input. {}", message))?;
+            if duration <= 0.0 {
+                return Err(format!("Invalid input. duration {} must be positive.", duration));
+            }
+            let h_burn = match parts.get(2) {
+                Some(text) => parse_field("hBurn", text).map_err(|message| format!("Invalid input. {}", message))?,
+                None => 0.0,
+            };
+            let c_burn = match parts.get(3) {
+                Some(text) => parse_field("cBurn", text).map_err(|message| format!("Invalid input. {}", message))?,
+                None => 0.0,
+            };
+            if parts.len() > 4 {
+                return Err("Invalid input. Usage: hold duration [hBurn [cBurn]]".to_string());
+            }
+            Ok(PilotCommand::Hold { duration, h_burn, c_burn })
+        }
+        "auto" => {
+            let duration = match parts.get(1) {
+                Some(text) if parts.len() == 2 => parse_field("duration", text).map_err(|message| format!("Invalid input. {}", message))?,
+                None => AUTOPILOT_TIMEOUT,
+                _ => return Err("Invalid input. Usage: auto [duration]".to_string()),
+            };
+            if duration <= 0.0 {
+                return Err(format!("Invalid input. duration {} must be positive.", duration));
+            }
+            Ok(PilotCommand::Auto(duration))
+        }
+        "pitch" => match parts.get(1) {
+            Some(text) if parts.len() == 2 => parse_field("pitch", text)
+                .map(PilotCommand::Pitch)
+                .map_err(|message| format!("Invalid input. {}", message)),
+            _ => Err("Invalid input. Usage: pitch degrees".to_string()),
+        },
+        "lpd" => {
+            let usage = "Usage: lpd [long|short|left|right [clicks] | reset]";
+            let clicks = match parts.get(2) {
+                Some(text) => parse_field("clicks", text).map_err(|message| format!("Invalid input. {}", message))?,
+                None => 1.0,
+            };
+            if parts.len() > 3 || clicks <= 0.0 || clicks > LPD_MAX_CLICKS {
+                return Err(format!("Invalid input. {}", usage));
+            }
+            let action = match parts.get(1).map(|word| word.to_ascii_lowercase()) {
+                None => LpdAction::Show,
+                Some(word) => match word.as_str() {
+                    "long" => LpdAction::Move { long: clicks, right: 0.0 },
+                    "short" => LpdAction::Move { long: -clicks, right: 0.0 },
+                    "right" => LpdAction::Move { long: 0.0, right: clicks },
+                    "left" => LpdAction::Move { long: 0.0, right: -clicks },
+                    "reset" if parts.len() == 2 => LpdAction::Reset,
+                    _ => return Err(format!("Invalid input. {}", usage)),
+                },
+            };
+            Ok(PilotCommand::Lpd(action))
+        }
+        "vburn" | "hburn" | "cburn" | "duration" => parse_adjustment(&parts)
+            .map(PilotCommand::Adjust)
+            .map_err(|message| format!("Invalid input. {}", message)),
+        word if word.starts_with(|c: char| c.is_ascii_alphabetic()) && word != "burn" && word != "until" => {
+            Err(format!("Unknown command '{}'. Type \"help\" for a list of commands.", parts[0]))
+        }
+        _ => parse_segment(input).map(PilotCommand::Burn).map_err(|message| format!("Invalid input. {}", message)),
+    }
 }
 
 fn main() {
-    let gravity = 5.33136483;       // ft/s^2 (lunar gravity)
-    let standard_gravity = 32.174;  // ft/s^2 (standard gravity)
-    let vex = 10000.0;              // ft/s (exhaust velocity)
-    let max_vertical_burn = 250.0;  // lbs/s
-    let max_horiz_burn = 10.0;      // lbs/s
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        print_usage();
+        return;
+    }
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            println!("{}", message);
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+
     let time_limit = 600.0;         // seconds
     let small_dt = 0.1;
 
     // --- Initial Conditions (Descent Stage)
-    let descent_dry_mass = 4700.0;  // lb (descent stage dry)
-    let mut descent_fuel_mass = 1500.0; // lb (descent fuel)
+    let scenario = options.scenario;
+    let descent_fuel_mass = scenario.descent_fuel; // lb (descent fuel, 1500 in the classic game)
 
     // --- Ascent Stage Parameters (for abort)
-    let ascent_dry_mass = 4850.0;   // lb (ascent stage dry)
-    let ascent_fuel_mass = 5187.0;  // lb (ascent fuel)
     let ascent_vex = 10000.0;       // ft/s
     let min_altitude_for_abort = 100.0; // ft (minimum altitude to safely abort)
 
-    // Total mass includes both stages
-    let mut mass = descent_dry_mass + descent_fuel_mass + ascent_dry_mass + ascent_fuel_mass; // 16,237 lbs
-    let mut altitude = 7500.0;                  // feet
-    let mass_slugs = mass / standard_gravity;   // Earth's Standard Gravity (32.174) in ft/s²
+    let mut vehicle = VehicleConfig::lunar_module();
+    vehicle.dps_envelope = options.engine_model;
+    vehicle.slosh = options.slosh;
+    if let Some((altitude, rate)) = options.radar_lock {
+        vehicle.radar_lock_altitude = altitude;
+        vehicle.radar_lock_rate = rate;
+    }
+    vehicle.throttle_tau = match options.throttle_tau {
+        Some(tau) => tau,
+        None if options.engine_model => 0.3,  // s
+        None => 0.0,
+    };
+    if scenario.spherical {
+        vehicle.max_pitch = 90.0;  // deg (the braking burn points the engine almost straight ahead)
+    }
+
+    // Total mass includes both stages and the RCS propellant
+    let mass = DESCENT_DRY_MASS + descent_fuel_mass + ASCENT_DRY_MASS + ASCENT_FUEL_MASS + vehicle.rcs_propellant; // 16,870 lbs
+    let mass_slugs = mass / STANDARD_GRAVITY;   // Earth's Standard Gravity (32.174) in ft/s²
 
     let target_touchdown_speed = 5.0; // ft/s
-    let mut rng = SimpleRng::new();
-    let mut v_down = 200.0 + rng.gen_range(0..=500) as f64; // 200-700 ft/s
-    let mut horiz_speed = 50.0 + rng.gen_range(0..=150) as f64; // 50-200 ft/s
-    let mut horiz_pos = 0.0;
-    let mut current_time = 0.0;
+    let mut rng = match options.seed {
+        Some(seed) => SimpleRng::with_seed(seed),
+        None => SimpleRng::new(),
+    };
+    let seed = rng.seed;
+    let dem_file = options.dem_file.as_deref().or(scenario.dem_file);
+    let terrain = if let Some(path) = dem_file {
+        let grid = match ElevationGrid::load(path) {
+            Ok(grid) => grid,
+            Err(message) => {
+                println!("{}", message);
+                std::process::exit(1);
+            }
+        };
+        let site = match (options.dem_site, options.dem_file.is_some()) {
+            (Some(site), _) => site,
+            (None, true) => grid.center(),
+            (None, false) => scenario.dem_site,
+        };
+        Terrain::from_dem(grid, site, options.dem_heading.unwrap_or(scenario.dem_heading), scenario)
+    } else if options.terrain {
+        Terrain::procedural(&mut SimpleRng::with_seed(seed ^ 0x7e44a1), scenario)
+    } else {
+        Terrain::flat()
+    };
+    let moon = Moon { terrain, gravity: GravityField { mascons: options.mascons.clone() }, spherical: scenario.spherical };
+    let terrain = &moon.terrain;
+    let mut lander = LanderState {
+        current_time: 0.0,
+        altitude: scenario.altitude,                                                // feet
+        v_down: rng.gen_range(scenario.v_down.0..=scenario.v_down.1) as f64,             // 200-700 ft/s (classic)
+        horiz_pos: 0.0,
+        horiz_speed: rng.gen_range(scenario.horiz_speed.0..=scenario.horiz_speed.1) as f64,  // 50-200 ft/s (classic)
+        cross_pos: 0.0,
+        cross_speed: rng.gen_range(scenario.cross_speed.0..=scenario.cross_speed.1) as f64,  // 0 ft/s (the classic game has no crossrange drift)
+        descent_fuel_mass,
+        mass,
+        pitch: 0.0,
+        pitch_rate: 0.0,
+        pitch_command: 0.0,
+        dps_ignited: false,
+        dps_level: 0.0,
+        dps_ignition_time: 0.0,
+        dps_lit: false,
+        rcs: RcsState { propellant: vehicle.rcs_propellant, jet_failed: [false; 16] },
+        slosh: SloshState::default(),
+        specific_force: [0.0; 3],
+    };
 
     // Tolerances for burn corrections (10% of max burns)
-    let v_burn_tolerance = 0.1 * max_vertical_burn; // 25 lbs/s (corrected from 20)
-    let h_burn_tolerance = 0.1 * max_horiz_burn;    // 1 lbs/s
+    let v_burn_tolerance = 0.1 * MAX_VERTICAL_BURN; // 25 lbs/s (corrected from 20)
+    let h_burn_tolerance = 0.1 * MAX_HORIZ_BURN;    // 1 lbs/s
 
     // Time Delay for feedback
     let transmission_delay = 2.6;   // Transmission time in seconds
     let processing_delay = 2.0;     // Processing time in seconds
 
-    // Correction queue
-    let mut pending_corrections: Vec<Correction> = Vec::with_capacity(100);
+    // Mission Control message queue
+    let mut pending_messages: Vec<Message> = Vec::with_capacity(MAX_PENDING_MESSAGES);
+    let message_delay = transmission_delay + processing_delay;  // s (from evaluation to the pilot hearing it)
+
+    // Remaining segments of a multi-segment burn schedule
+    let mut scheduled_segments: VecDeque<BurnSegment> = VecDeque::new();
+    let mut schedule_total = 0;
+
+    // Last burn flown, for "repeat" and relative adjustments
+    let mut last_segment: Option<BurnSegment> = None;
+
+    // Designated landing site (moved with the LPD)
+    let mut site = (scenario.target_pos, scenario.target_cross);
+
+    // Cockpit instruments (drawn after the initial conditions so seeds repeat)
+    let mut instruments = Instruments {
+        gauge: FuelGauge::new(options.gauging, descent_fuel_mass, &vehicle, &mut rng),
+        radar: LandingRadar::new(options.radar, &mut rng),
+        nav: Navigation::new(options.nav, &lander, &vehicle, &mut rng),
+        dust: DustModel::new(options.dust, &mut rng),
+    };
+    let mut failures = FailureInjector::new(options.failure_rates, options.scripted_failures.clone(), &mut rng);
+    let mut agc = AgcExecutive::new(scenario.agc_alarms, &mut rng);
+    let mut recorder = match FlightRecorder::new(options.record_file.as_deref()) {
+        Ok(recorder) => recorder,
+        Err(message) => {
+            println!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    // The autopilot flies the braking phase until it hands over at high gate
+    let mut high_gate_passed = false;
+
+    // Lit when one of the 67-inch probes below the footpads touches the surface: time and descent rate
+    let mut contact_light: Option<(f64, f64)> = None;
+    let mut engine_stopped = false;          // "stop" (or the autopilot) shut down the descent engine
+    let mut engine_off_time: Option<f64> = None;  // s (engine first found off after contact light)
 
     // Game Introduction 
     println!("=========================================================");
@@ -176,30 +3018,52 @@ fn main() {
     println!("Welcome to the Apollo Lunar Lander Simulator!");
     println!("Version 1.0.0 - Created by George McGinn - 04/10/2025");
     println!();
-    println!("You are the pilot of the Lunar Module (LEM) when the AGC");
-    println!("fails at 7500 ft due to prior burn errors. Initial speeds");
-    println!("are random (vDown: 200-700 ft/s, horizSpeed: 50-200 ft/s).");
+    if scenario.name == "agc-failure" {
+        println!("You are the pilot of the Lunar Module (LEM) when the AGC");
+        println!("fails at 7500 ft due to prior burn errors. Initial speeds");
+        println!("are random (vDown: 200-700 ft/s, horizSpeed: 50-200 ft/s).");
+    } else {
+        println!("Scenario {}: {}.", scenario.name, scenario.description);
+    }
     println!();
     println!("Starting Conditions:");
-    println!("  - Altitude: 7,500 ft");
-    println!("  - Vertical Speed: {:.2} ft/s (downward)", v_down);
-    println!("  - Horizontal Speed: {:.2} ft/s", horiz_speed);
-    println!("  - Descent Fuel: {:.2} lbs", descent_fuel_mass);
-    println!("  - Ascent Fuel (for abort): {:.0} lbs", ascent_fuel_mass);
-    println!("  - Descent Dry Mass: {:.0} lbs", descent_dry_mass);
-    println!("  - Ascent Dry Mass: {:.0} lbs", ascent_dry_mass);
-    println!("  - Total Mass: {:.0} lbs", mass);
+    println!("  - Altitude: {:.0} ft", scenario.altitude);
+    println!("  - Vertical Speed: {:.2} ft/s (downward)", lander.v_down);
+    println!("  - Horizontal Speed: {:.2} ft/s", lander.horiz_speed);
+    println!("  - Crossrange Speed: {:.2} ft/s", lander.cross_speed);
+    println!("  - Landing Site: {:.0} ft downrange, {:.0} ft crossrange", scenario.target_pos, scenario.target_cross);
+    if let Some(path) = dem_file {
+        println!("  - Terrain: elevation grid {}", path);
+    } else if !terrain.is_flat() {
+        println!("  - Terrain: {} craters, {} boulders (seed {})", terrain.craters.len(), terrain.boulders.len(), seed);
+    }
+    if !moon.gravity.mascons.is_empty() {
+        println!("  - Gravity: {} mascon(s)", moon.gravity.mascons.len());
+    }
+    println!("  - Descent Fuel: {:.2} lbs", lander.descent_fuel_mass);
+    println!("  - Ascent Fuel (for abort): {:.0} lbs", ASCENT_FUEL_MASS);
+    println!("  - RCS Propellant (hBurn and attitude): {:.0} lbs", vehicle.rcs_propellant);
+    println!("  - Descent Dry Mass: {:.0} lbs", DESCENT_DRY_MASS);
+    println!("  - Ascent Dry Mass: {:.0} lbs", ASCENT_DRY_MASS);
+    println!("  - Total Mass: {:.0} lbs", lander.mass);
     println!("  - Total Mass Slugs: {:.3}", mass_slugs);
     println!();
     println!("Controls:");
     println!("  - Duration: Time (seconds) to apply burns (-1 to abort)");
     println!("  - vBurn: Vertical burn rate (0 to 250 lbs/s)");
     println!("  - hBurn: Horizontal burn rate (-10 to 10 lbs/s)");
+    println!("  - cBurn: Optional fourth number, crossrange burn rate (-10 to 10 lbs/s)");
+    println!("  - Schedule: Several \"duration vBurn hBurn\" segments separated by ';'");
+    println!("  - until vdown<=50 vburn=200 hburn=-3: Burn until alt, vdown or hspeed reaches a value");
+    println!("  - project duration vBurn hBurn: Preview a burn without flying it");
+    println!("  - Type \"help\" at the prompt for all commands (hold, repeat, vburn +10, ...)");
     println!();
     println!("Objective:");
-    println!("Land with vDown <= 5 ft/s and |horizSpeed| <= 5 ft/s for a");
-    println!("perfect landing. Receive feedback after each burn to adjust");
-    println!("your trajectory. Abort if needed, but ensure you can reach orbit!");
+    println!("Land with vDown <= 5 ft/s and a total horizontal speed (downrange and");
+    println!("crossrange) <= 5 ft/s for a perfect landing, as close to the landing");
+    println!("site as you can (ToGo shows the distance left). Receive feedback after");
+    println!("each burn to adjust your trajectory. Abort if needed, but ensure you can");
+    println!("reach orbit!");
     println!();
     println!("Note: The game also enforces a time delay that the real astronauts.");
     println!("      experienced. The time delay is {:.1} seconds (signal delay of", transmission_delay + processing_delay);
@@ -211,55 +3075,399 @@ fn main() {
     println!();
     sleep(Duration::from_secs(5));
 
-    println!("Lunar Lander: AGC failed. Altitude={:.0} ft, vDown={:.0} ft/s, hSpeed={:.0} ft/s", altitude, v_down, horiz_speed);
-    println!("Input duration (s, -1 to abort), vBurn (0-250), hBurn (-10 to 10), separated by spaces");
+    println!("Lunar Lander: {}. Altitude={:.0} ft, vDown={:.0} ft/s, hSpeed={:.0} ft/s", scenario.event, lander.altitude, lander.v_down, lander.horiz_speed);
+    println!("Input duration (s, -1 to abort), vBurn (0-250), hBurn (-10 to 10) and optional cBurn (-10 to 10), separated by spaces");
+    println!("Type \"help\" for more commands");
+    if scenario.spherical {
+        println!("Type \"auto\" to let the autopilot fly the braking phase to high gate ({:.0} ft)", HIGH_GATE_ALTITUDE);
+    }
+    if agc.enabled {
+        println!("The AGC is flying the approach. Press Enter to let it fly another {:.0} s; a burn command takes over manually.",
+            AGC_SEGMENT);
+    }
+    if let Some(message) = instruments.radar.update(&lander, &vehicle, terrain, 0.0) {
+        println!("{}", message);
+    }
+    instruments.nav.radar_update(&instruments.radar, &vehicle, terrain);
+    recorder.record(&lander, &instruments);
+    recorder.log_event(&lander, &instruments, scenario.event);
 
-    while altitude > 0.0 {
+    while height_above_terrain(&lander, terrain) > 0.0 {
     
         // Step 1: Display Current State
-        if descent_fuel_mass == 0.0 {
+        if lander.descent_fuel_mass == 0.0 {
             println!("\x1b[31m[WARNING] DESCENT FUEL HAS RUN OUT. CONSIDER ABORT PROCESS.\x1b[0m");
         }
-        println!("t={:.2}s  Alt={:.3}  vDown={:.3}  hPos={:.3}  hSpeed={:.3}  Fuel={:.3}", 
-            current_time, altitude, v_down, horiz_pos, horiz_speed, descent_fuel_mass);
+        if lander.rcs.propellant == 0.0 {
+            println!("\x1b[31m[WARNING] RCS PROPELLANT DEPLETED. NO TRANSLATION OR ATTITUDE CONTROL.\x1b[0m");
+        }
+        print_state_line(&lander, &vehicle, site, terrain, &mut instruments);
 
-        // Step 2: Prompt for User Input
-        let (duration, v_burn, h_burn) = loop {
-            print!(">> ");
+        // Step 2a: Continue a burn schedule on Enter; any other command cancels the remainder and is then carried out
+        let mut next_segment = None;
+        let mut typed_command = None;
+        if let Some(segment) = scheduled_segments.front().copied() {
+            print!("Schedule segment {}/{}: {} (Enter to continue, \"cancel\" or another command to stop) >> ",
+                schedule_total - scheduled_segments.len() + 1, schedule_total, describe_segment(&segment));
             io::stdout().flush().unwrap();
             let mut input = String::new();
-            io::stdin().read_line(&mut input).expect("Failed to read line");
-            let parts: Vec<&str> = input.trim().split_whitespace().collect();
-            if parts.len() != 3 {
-                println!("Invalid input. Please enter three numbers separated by spaces.");
-                continue;
+            if io::stdin().read_line(&mut input).expect("Failed to read line") == 0 {
+                println!();
+                println!("End of input. Ending simulation.");
+                return;
             }
-            match (parts[0].parse::<f64>(), parts[1].parse::<f64>(), parts[2].parse::<f64>()) {
-                (Ok(d), Ok(v), Ok(h)) => break (d, v, h),
-                _ => {
-                    println!("Invalid input. Please enter valid numbers.");
-                    continue;
+            if input.trim().is_empty() {
+                next_segment = scheduled_segments.pop_front();
+            } else {
+                println!("Schedule cancelled. {} segment(s) dropped.", scheduled_segments.len());
+                scheduled_segments.clear();
+                if !input.trim().eq_ignore_ascii_case("cancel") {
+                    typed_command = Some(input);
+                }
+            }
+        }
+
+        // Step 2b: Prompt for User Input
+        let segment = if let Some(segment) = next_segment {
+            segment
+        } else {
+            loop {
+                let input = if let Some(input) = typed_command.take() {
+                    input
+                } else {
+                    print!("{}", if agc.in_control { "AGC >> " } else { ">> " });
+                    io::stdout().flush().unwrap();
+                    let mut input = String::new();
+                    if io::stdin().read_line(&mut input).expect("Failed to read line") == 0 {
+                        println!();
+                        println!("End of input. Ending simulation.");
+                        return;
+                    }
+                    input
+                };
+                if agc.in_control && input.trim().is_empty() {
+                    break BurnSegment {
+                        duration: AGC_SEGMENT, v_burn: 0.0, h_burn: 0.0, c_burn: 0.0, until: None, hover: false, autopilot: true,
+                    };
+                }
+                match parse_command(&input) {
+                    Ok(PilotCommand::Burn(segment)) => break segment,
+                    Ok(PilotCommand::Abort) => {
+                        break BurnSegment {
+                            duration: -1.0, v_burn: 0.0, h_burn: 0.0, c_burn: 0.0, until: None, hover: false, autopilot: false,
+                        };
+                    }
+                    Ok(PilotCommand::Status) => {
+                        print_state_line(&lander, &vehicle, site, terrain, &mut instruments);
+                        match last_segment {
+                            Some(last) => println!("Last burn: {}", describe_segment(&last)),
+                            None => println!("Last burn: none"),
+                        }
+                        let estimate = instruments.nav.estimate(&lander);
+                        let (downrange, crossrange) = distance_to_go(&estimate, site);
+                        println!("Landing site: {:.0} ft to go ({:.0} ft downrange, {:.0} ft crossrange)",
+                            downrange.hypot(crossrange), downrange, crossrange);
+                        if site != (scenario.target_pos, scenario.target_cross) {
+                            println!("Landing site redesignated to {:.0} ft downrange, {:.0} ft crossrange", site.0, site.1);
+                        }
+                        if !terrain.is_flat() {
+                            println!("Terrain below: elevation {:.1} ft, slope {:.1} deg, tallest boulder within {:.0} ft: {:.1} ft",
+                                terrain.elevation(estimate.horiz_pos, estimate.cross_pos), terrain.slope(estimate.horiz_pos, estimate.cross_pos),
+                                GEAR_RADIUS, terrain.tallest_boulder(estimate.horiz_pos, estimate.cross_pos, GEAR_RADIUS));
+                        }
+                        println!("Pitch: {:.2} deg (command {:.1} deg, rate {:.2} deg/s)",
+                            lander.pitch.to_degrees(), lander.pitch_command.to_degrees(), lander.pitch_rate.to_degrees());
+                        println!("Hover burn rate: {:.2} lbs/s (changes as fuel burns)", hover_burn_rate(lander.mass));
+                        if vehicle.slosh {
+                            println!("Propellant slosh: {:.1} deg pitch plane, {:.1} deg crossrange",
+                                lander.slosh.angle.0.to_degrees(), lander.slosh.angle.1.to_degrees());
+                        }
+                        if !moon.gravity.mascons.is_empty() {
+                            let (down, along, across) = moon.gravity.acceleration(&lander);
+                            println!("Local gravity: {:.5} ft/s^2 down ({:+.0} mGal), {:+.5} downrange, {:+.5} crossrange",
+                                down, (down - GRAVITY) / FT_S2_PER_MGAL, along, across);
+                        }
+                        println!("Thrust-to-weight: {:.2} at max vBurn", thrust_to_weight(max_v_burn(&vehicle), lander.mass));
+                        if let Some(last) = last_segment {
+                            if !last.hover && !last.autopilot {
+                                println!("Thrust-to-weight: {:.2} at last vBurn", thrust_to_weight(last.v_burn, lander.mass));
+                            }
+                        }
+                        let fuel = instruments.gauge.read(&lander, &vehicle).min(lander.mass - 1.0);
+                        println!("Descent delta-v remaining: {:.1} ft/s", descent_delta_v(lander.mass, fuel));
+                        if instruments.gauge.enabled {
+                            println!("Descent propellant (indicated): {:.0} lbs, about {:.0} s of hover time{}", fuel,
+                                fuel / hover_burn_rate(lander.mass), if instruments.gauge.low_level { ", LOW LEVEL light on" } else { "" });
+                        }
+                        if instruments.radar.enabled {
+                            let radar = &instruments.radar;
+                            match (radar.locked, radar.reading) {
+                                (true, Some((altitude, v_down))) => println!("Landing radar: locked, {:.1} ft above the terrain, vDown {:.1} ft/s, \
+                                    hSpeed {:.1} ft/s, cSpeed {:.1} ft/s", altitude, v_down, radar.velocity.0, radar.velocity.1),
+                                _ => println!("Landing radar: no lock (locks on below {:.0} ft and {:.0} ft/s descent rate)",
+                                    vehicle.radar_lock_altitude, vehicle.radar_lock_rate),
+                            }
+                        }
+                        let active = failures.describe(&lander, &instruments.radar);
+                        if !active.is_empty() {
+                            println!("\x1b[31mFailures: {}\x1b[0m", active.join(", "));
+                        }
+                        if agc.enabled {
+                            println!("{}", agc.describe());
+                        }
+                        println!("Mission Control messages pending: {}", pending_messages.len());
+                        continue;
+                    }
+                    Ok(PilotCommand::Pitch(degrees)) => {
+                        if !(-vehicle.max_pitch..=vehicle.max_pitch).contains(&degrees) {
+                            println!("Invalid pitch. pitch {} is out of range. Allowed: -{:.0} to {:.0} degrees.",
+                                degrees, vehicle.max_pitch, vehicle.max_pitch);
+                            continue;
+                        }
+                        lander.pitch_command = degrees.to_radians();
+                        println!("Pitch command set to {:.1} degrees (now {:.1}). The RCS slews at up to {:.0} deg/s.",
+                            degrees, lander.pitch.to_degrees(), vehicle.max_pitch_rate);
+                        continue;
+                    }
+                    Ok(PilotCommand::Help) => {
+                        print_command_help();
+                        continue;
+                    }
+                    Ok(PilotCommand::Lpd(action)) => {
+                        // Predicted landing point with the last burn held, from the state the LEM's computer
+                        // knows, as in Step 6
+                        let estimate = instruments.nav.estimate(&lander);
+                        let (v_last, h_last, c_last) = match last_segment {
+                            Some(last) if last.hover => (hover_burn_rate(lander.mass), last.h_burn, last.c_burn),
+                            Some(last) if last.autopilot => (lander.dps_level, 0.0, 0.0),
+                            Some(last) => (last.v_burn, last.h_burn, last.c_burn),
+                            None => (0.0, 0.0, 0.0),
+                        };
+                        let projection = project_burn(&estimate, &vehicle, &moon, v_last, h_last, c_last, f64::INFINITY);
+                        let predicted = if projection.landed {
+                            Some((projection.state.horiz_pos, projection.state.cross_pos))
+                        } else {
+                            None
+                        };
+                        let t_go = if projection.landed {
+                            projection.elapsed
+                        } else {
+                            height_above_terrain(&estimate, terrain) / estimate.v_down.max(1.0)
+                        };
+                        match action {
+                            LpdAction::Move { long, right } => {
+                                let offset = (long * LPD_INCREMENT, right * LPD_INCREMENT);
+                                site = (site.0 + offset.0, site.1 + offset.1);
+                                let (delta_v, rcs) = redesignation_cost(&estimate, &vehicle, offset, t_go);
+                                let shift = if offset.0 != 0.0 {
+                                    format!("{:.0} ft {}", offset.0.abs(), if offset.0 > 0.0 { "long" } else { "short" })
+                                } else {
+                                    format!("{:.0} ft {}", offset.1.abs(), if offset.1 > 0.0 { "right" } else { "left" })
+                                };
+                                println!("Landing site redesignated {}.", shift);
+                                println!("Estimated cost with {:.0} s to go: {:.1} ft/s of horizontal delta-v, about {:.1} lbs of RCS propellant.",
+                                    t_go, delta_v, rcs);
+                            }
+                            LpdAction::Reset => {
+                                site = (scenario.target_pos, scenario.target_cross);
+                                println!("Landing site restored to the planned site.");
+                            }
+                            LpdAction::Show => {}
+                        }
+                        let (downrange, crossrange) = distance_to_go(&estimate, site);
+                        println!("LPD: designated site {:.0} ft downrange, {:.0} ft crossrange ({:.0} ft to go).",
+                            site.0, site.1, downrange.hypot(crossrange));
+                        match predicted {
+                            Some((x, y)) => {
+                                let (long, right) = (x - site.0, y - site.1);
+                                println!("LPD: predicted touchdown at t={:.1}s is {:.0} ft {} and {:.0} ft {} of the site (last burn held).",
+                                    lander.current_time + projection.elapsed, long.abs(), if long >= 0.0 { "long" } else { "short" },
+                                    right.abs(), if right >= 0.0 { "right" } else { "left" });
+                                match site_hazard(terrain, x, y) {
+                                    Some(hazard) => println!("\x1b[31mLPD: predicted touchdown point hazard: {}.\x1b[0m", hazard.describe()),
+                                    None => println!("LPD: predicted touchdown point is clear."),
+                                }
+                            }
+                            None => println!("LPD: no touchdown predicted with the last burn held."),
+                        }
+                        match site_hazard(terrain, site.0, site.1) {
+                            Some(hazard) => println!("\x1b[31mLPD: designated site hazard: {}.\x1b[0m", hazard.describe()),
+                            None => println!("LPD: designated site is clear."),
+                        }
+                        print_lpd_map(terrain, predicted.unwrap_or(site), predicted, site);
+                        continue;
+                    }
+                    Ok(PilotCommand::Hold { duration, h_burn, c_burn }) => {
+                        let v_burn = hover_burn_rate(lander.mass);
+                        if clamp_to_envelope(v_burn, &vehicle) != v_burn {
+                            println!("Cannot hold: the hover burn rate {:.2} lbs/s is outside the DPS throttle envelope \
+                                (nearest rate it can fly: {:.2} lbs/s), so a hover is impossible.",
+                                v_burn, clamp_to_envelope(v_burn, &vehicle));
+                            continue;
+                        }
+                        println!("Holding at hover burn rate vBurn={:.2} lbs/s for {:.1}s (auto-throttled as fuel burns).",
+                            v_burn, duration);
+                        let fuel = instruments.gauge.read(&lander, &vehicle).min(lander.mass - 1.0);
+                        println!("Descent delta-v remaining: {:.1} ft/s, hover time available: {:.1} s",
+                            descent_delta_v(lander.mass, fuel), fuel / v_burn);
+                        break BurnSegment { duration, v_burn, h_burn, c_burn, until: None, hover: true, autopilot: false };
+                    }
+                    Ok(PilotCommand::Auto(duration)) => {
+                        if agc.failed {
+                            println!("The AGC is down after Mission Control's NO-GO call. Fly the LEM manually.");
+                            continue;
+                        }
+                        if !high_gate_passed && lander.altitude > HIGH_GATE_ALTITUDE {
+                            println!("Autopilot engaged: braking phase to high gate ({:.0} ft, {:.0} ft short of the site).",
+                                HIGH_GATE_ALTITUDE, HIGH_GATE_RANGE);
+                        } else {
+                            println!("Autopilot engaged: approach to the designated site, then a vertical descent from 100 ft.");
+                        }
+                        break BurnSegment {
+                            duration, v_burn: 0.0, h_burn: 0.0, c_burn: 0.0, until: None, hover: false, autopilot: true,
+                        };
+                    }
+                    Ok(PilotCommand::EngineStop) => {
+                        if engine_stopped {
+                            println!("The descent engine is already stopped.");
+                            continue;
+                        }
+                        if height_above_terrain(&lander, terrain) > ENGINE_STOP_ALTITUDE {
+                            println!("Engine stop is for touchdown. Above {:.0} ft use vBurn 0 instead.", ENGINE_STOP_ALTITUDE);
+                            continue;
+                        }
+                        engine_stopped = true;
+                        recorder.log_event(&lander, &instruments, "Engine stop");
+                        match contact_light {
+                            Some((time, _)) => println!("ENGINE STOP at t={:.2}s, {:.1} s after contact light.",
+                                lander.current_time, lander.current_time - time),
+                            None => println!("\x1b[33mENGINE STOP at t={:.2}s before contact light: the LEM will drop {:.1} ft.\x1b[0m",
+                                lander.current_time, height_above_terrain(&lander, terrain)),
+                        }
+                        // Fall to the surface (far longer than a drop from 50 ft takes)
+                        break BurnSegment {
+                            duration: 10.0, v_burn: 0.0, h_burn: 0.0, c_burn: 0.0, until: None, hover: false, autopilot: false,
+                        };
+                    }
+                    Ok(PilotCommand::Repeat) => match last_segment {
+                        Some(last) => {
+                            println!("Repeating: {}", describe_segment(&last));
+                            break last;
+                        }
+                        None => {
+                            println!("Nothing to repeat yet. Enter a burn first.");
+                            continue;
+                        }
+                    },
+                    Ok(PilotCommand::Adjust(adjustment)) => {
+                        let Some(last) = last_segment else {
+                            println!("No previous burn to adjust. Enter a burn first.");
+                            continue;
+                        };
+                        let mut adjusted = last;
+                        if let Some(change) = adjustment.duration {
+                            adjusted.duration = change.apply(last.duration);
+                        }
+                        if let Some(change) = adjustment.v_burn {
+                            adjusted.v_burn = change.apply(last.v_burn);
+                            adjusted.hover = false;
+                            adjusted.autopilot = false;
+                        }
+                        if let Some(change) = adjustment.h_burn {
+                            adjusted.h_burn = change.apply(last.h_burn);
+                        }
+                        if let Some(change) = adjustment.c_burn {
+                            adjusted.c_burn = change.apply(last.c_burn);
+                        }
+                        if adjusted.duration <= 0.0 {
+                            println!("Invalid adjustment. duration {} must be positive (use \"abort\" to abort).", adjusted.duration);
+                            continue;
+                        }
+                        if let Err(message) = check_burn_rates(adjusted.v_burn, adjusted.h_burn, adjusted.c_burn) {
+                            println!("Invalid adjustment. {}", message);
+                            continue;
+                        }
+                        println!("Burn: {}", describe_segment(&adjusted));
+                        break adjusted;
+                    }
+                    Ok(PilotCommand::Schedule(segments)) => {
+                        // Every segment must be flyable, so none is dropped once the schedule runs
+                        let rejected = segments.iter().enumerate().find_map(|(index, segment)| {
+                            throttle_envelope(segment.v_burn, &vehicle).err().map(|message| (index, message))
+                        });
+                        if let Some((index, message)) = rejected {
+                            println!("Invalid segment {}. {}", index + 1, message);
+                            continue;
+                        }
+                        schedule_total = segments.len();
+                        scheduled_segments = segments.into_iter().collect();
+                        let first = scheduled_segments.pop_front().unwrap();
+                        println!("Executing schedule of {} segment(s). Segment 1/{}: {}",
+                            schedule_total, schedule_total, describe_segment(&first));
+                        break first;
+                    }
+                    Ok(PilotCommand::Project { duration, v_burn, h_burn, c_burn }) => {
+                        if duration <= 0.0 {
+                            println!("Invalid what-if burn. duration {} must be positive.", duration);
+                            continue;
+                        }
+                        if let Err(message) = check_burn_rates(v_burn, h_burn, c_burn) {
+                            println!("Invalid what-if burn. {}", message);
+                            continue;
+                        }
+                        let v_burn = match throttle_envelope(v_burn, &vehicle) {
+                            Ok((allowed, note)) => {
+                                if let Some(note) = note {
+                                    println!("\x1b[33m{}\x1b[0m", note);
+                                }
+                                allowed
+                            }
+                            Err(message) => {
+                                println!("Invalid what-if burn. {}", message);
+                                continue;
+                            }
+                        };
+                        let what_if = project_what_if(&instruments.nav.estimate(&lander), &vehicle, &moon, duration, v_burn, h_burn, c_burn);
+                        if options.difficulty == Difficulty::Hard {
+                            // On Hard, the projection is worked by the ground and arrives after the usual delay
+                            if send_message(&mut pending_messages, lander.current_time, message_delay, MessageKind::WhatIf(Box::new(what_if))) {
+                                println!("What-if request sent to Mission Control. Expect an answer at t={:.2}s.",
+                                    lander.current_time + message_delay);
+                            } else {
+                                println!("Mission Control is busy. What-if request not sent.");
+                            }
+                        } else {
+                            report_what_if(&what_if, lander.current_time, "[What-if]");
+                        }
+                        continue;
+                    }
+                    Err(message) => {
+                        println!("{}", message);
+                        continue;
+                    }
                 }
             }
         };
 
+        let (duration, mut v_burn, h_burn, mut c_burn) = (segment.duration, segment.v_burn, segment.h_burn, segment.c_burn);
+
         // Step 3: Handle Abort Condition
         if duration < 0.0 {
             println!("Aborting landing...");
-            if altitude < min_altitude_for_abort {
+            if height_above_terrain(&lander, terrain) < min_altitude_for_abort {
                 println!("\x1b[31mToo low to abort safely! Crashing into the surface.\x1b[0m");
-                altitude = 0.0;
+                lander.altitude = terrain.elevation(lander.horiz_pos, lander.cross_pos);
                 break;
-            } else if ascent_fuel_mass <= 0.0 {
+            } else if ASCENT_FUEL_MASS <= 0.0 {
                 println!("\x1b[31mNo ascent fuel left! Cannot reach orbit. Crashing.\x1b[0m");
-                altitude = 0.0;
+                lander.altitude = terrain.elevation(lander.horiz_pos, lander.cross_pos);
                 break;
             } else {
-//                let delta_v = ascent_vex * ((ascent_dry_mass + ascent_fuel_mass) / ascent_dry_mass).ln();
-                let mass_ratio: f64 = (ascent_dry_mass + ascent_fuel_mass) / ascent_dry_mass;
+//                let delta_v = ascent_vex * ((ASCENT_DRY_MASS + ASCENT_FUEL_MASS) / ASCENT_DRY_MASS).ln();
+                let mass_ratio: f64 = (ASCENT_DRY_MASS + ASCENT_FUEL_MASS) / ASCENT_DRY_MASS;
                 let delta_v: f64 = ascent_vex * mass_ratio.ln();
                 let lunar_orbital_speed = 5512.0;  // ft/s (approximate speed for lunar orbit)
-                if delta_v >= lunar_orbital_speed - horiz_speed.abs() {
+                if delta_v >= lunar_orbital_speed - lander.horiz_speed.abs() {
                     println!("\x1b[32mAbort successful! Achieved lunar orbit with {:.2} ft/s delta-v.\x1b[0m", delta_v);
                     break;
                 } else {
@@ -270,111 +3478,261 @@ fn main() {
         }
 
         // Step 4: Validate User Inputs 
-        if v_burn < 0.0 || v_burn > max_vertical_burn || h_burn < -max_horiz_burn || h_burn > max_horiz_burn {
-            println!("Invalid burn rates.");
+        if let Err(message) = check_burn_rates(v_burn, h_burn, c_burn) {
+            println!("Invalid burn rates. {}", message);
             continue;
         }
-
-        // Step 5: Simulate the Burn Duration
-        let steps = (duration / small_dt) as i32;
-        for _ in 0..steps {
-            if altitude <= 0.0 {
-                break;
+        if vehicle.dps_envelope && duration >= 0.0 && !segment.autopilot {
+            match throttle_envelope(v_burn, &vehicle) {
+                Ok((allowed, note)) => {
+                    if let Some(note) = note {
+                        println!("\x1b[33m{}\x1b[0m", note);
+                    }
+                    v_burn = allowed;
+                }
+                Err(message) => {
+                    println!("Burn rejected. {}", message);
+                    continue;
+                }
+            }
+        }
+        if let Some(condition) = segment.until {
+            if condition.is_met(&instruments.nav.estimate(&lander)) {
+                println!("Condition {} is already met. No burn performed.", condition.describe());
+                continue;
             }
-            let fuel_used = (v_burn + h_burn.abs()) * small_dt;
-            let fuel_used = if fuel_used > descent_fuel_mass { descent_fuel_mass } else { fuel_used };
-            descent_fuel_mass -= fuel_used;
-            mass = descent_dry_mass + descent_fuel_mass + ascent_dry_mass + ascent_fuel_mass;
-            let mass_slugs = mass / standard_gravity;   // Earth's Standard Gravity (32.174) in ft/s²
+        }
 
-            // Vertical thrust
-            let mass_flow_rate_vertical = v_burn / standard_gravity;  // slugs/s
-            let thrust_force_vertical = mass_flow_rate_vertical * vex;  // lbs
-            let thrust_acc_vertical = thrust_force_vertical / mass_slugs;  // ft/s² upward
-            let net_acc_vertical = gravity - thrust_acc_vertical;  // gravity downward
+        // Autopilot segments ("auto", or repeated and adjusted ones) are flown by the AGC, if it is still GO
+        if agc.enabled && segment.autopilot && duration >= 0.0 {
+            if agc.failed {
+                println!("The AGC is down after Mission Control's NO-GO call. Fly the LEM manually.");
+                continue;
+            }
+            if !agc.in_control {
+                println!("AGC back in control at t={:.2}s.", lander.current_time);
+                agc.in_control = true;
+                recorder.log_event(&lander, &instruments, "AGC back in control");
+            }
+        }
 
-            // Horizontal thrust
-            let mass_flow_rate_horizontal = h_burn.abs() / standard_gravity;  // slugs/s
-            let thrust_force_horizontal = mass_flow_rate_horizontal * vex;  // lbs
-            let thrust_acc_horizontal = (if h_burn >= 0.0 { 1.0 } else { -1.0 }) * thrust_force_horizontal / mass_slugs;  // ft/s²
+        // The segment is accepted: remember it for "repeat" and hand control to the pilot if the AGC was flying
+        if duration >= 0.0 {
+            last_segment = Some(segment);
+            if agc.in_control && !segment.autopilot {
+                println!("Manual takeover at t={:.2}s. The AGC keeps monitoring; type \"auto\" to hand control back.",
+                    lander.current_time);
+                recorder.log_event(&lander, &instruments, "Manual takeover");
+                agc.in_control = false;
+            }
+        }
+        if vehicle.dps_envelope && duration >= 0.0 && !segment.autopilot {
+            if v_burn > 0.0 && !lander.dps_ignited && lander.descent_fuel_mass > 0.0 {
+                println!("DPS ignition.");
+            } else if v_burn == 0.0 && lander.dps_ignited {
+                println!("DPS shutdown.");
+            }
+        }
 
-            rk4_vertical(&mut altitude, &mut v_down, small_dt, net_acc_vertical);
-            rk4_horizontal(&mut horiz_pos, &mut horiz_speed, small_dt, thrust_acc_horizontal);
-            current_time += small_dt;
+        // Step 5: Simulate the Burn Duration (an "until" burn stops once its condition is met)
+        let steps = (duration / small_dt) as i32;
+        let mut condition_met = false;
+        let mut contact_interrupt = false;
+        let mut agc_interrupt = false;  // Mission Control called NO-GO while the AGC was flying
+        let braking = !high_gate_passed && lander.altitude > HIGH_GATE_ALTITUDE;
+        for step in 0..steps {
+            if height_above_terrain(&lander, terrain) <= 0.0 || condition_met || contact_interrupt || agc_interrupt {
+                break;
+            }
+            if segment.autopilot {
+                if step > 0 && step % 300 == 0 {
+                    print_state_line(&lander, &vehicle, site, terrain, &mut instruments);
+                }
+                if let Some(alarm) = agc.step(lander.current_time, small_dt) {
+                    let message = format!("PROGRAM ALARM {}", alarm.code);
+                    println!("\x1b[31m{} at t={:.2}s: executive overflow, the AGC restarted.\x1b[0m", message, lander.current_time);
+                    recorder.log_event(&lander, &instruments, &message);
+                    send_message(&mut pending_messages, lander.current_time, message_delay, MessageKind::ProgramAlarm(alarm));
+                }
+                // After a restart the guidance cycles are lost and the last commands are held
+                if step == 0 || !agc.restarting(lander.current_time) {
+                    let estimate = instruments.nav.estimate(&lander);
+                    let command = autopilot_command(&estimate, &vehicle, &moon, site, braking);
+                    if braking && (command.t_go <= 0.0 || estimate.altitude <= HIGH_GATE_ALTITUDE) {
+                        println!("\x1b[32mHigh gate at t={:.2}s: altitude {:.0} ft, {:.0} ft to go, hSpeed {:.0} ft/s, vDown {:.0} ft/s. \
+                            Autopilot handing over to the pilot.\x1b[0m",
+                            lander.current_time, estimate.altitude, distance_to_go(&estimate, site).0, estimate.horiz_speed, estimate.v_down);
+                        high_gate_passed = true;
+                        break;
+                    }
+                    // Guidance gets too sensitive in the last 10 s before high gate, so the last
+                    // commands are held until it is reached
+                    if !braking || command.t_go > 10.0 {
+                        v_burn = command.v_burn;
+                        c_burn = command.c_burn;
+                        lander.pitch_command = command.pitch;
+                    }
+                }
+            }
+            if segment.hover {
+                // Automatic hover throttle: follow the hover rate as the LEM gets lighter
+                v_burn = clamp_to_envelope(hover_burn_rate(lander.mass), &vehicle);
+            }
+            if engine_stopped {
+                v_burn = 0.0;
+            }
+            let was_ignited = lander.dps_ignited;
+            let had_rcs = lander.rcs.propellant > 0.0;
+            let before = lander;
+            step_lander(&mut lander, &vehicle, &moon, failures.throttle(v_burn), h_burn, c_burn, small_dt);
+            instruments.nav.propagate(&before, &lander, &vehicle, moon.spherical, small_dt);
+            if was_ignited && !lander.dps_ignited && lander.descent_fuel_mass <= 0.0 {
+                println!("\x1b[31mDPS flameout at t={:.2}s: descent propellant depleted.\x1b[0m", lander.current_time);
+            }
+            if had_rcs && lander.rcs.propellant <= 0.0 {
+                println!("\x1b[31mRCS failure at t={:.2}s: RCS propellant exhausted. Translation and attitude control lost.\x1b[0m",
+                    lander.current_time);
+            }
+            if let Some(message) = instruments.radar.update(&lander, &vehicle, terrain, small_dt) {
+                println!("{}", message);
+            }
+            for alarm in failures.step(&mut lander, &mut instruments.radar, terrain, small_dt) {
+                let color = if alarm.starts_with("[ALARM]") { 31 } else { 32 };
+                println!("\x1b[{}m{} (t={:.2}s)\x1b[0m", color, alarm, lander.current_time);
+                recorder.log_event(&lander, &instruments, &alarm);
+            }
+            instruments.nav.radar_update(&instruments.radar, &vehicle, terrain);
+            if let Some(message) = instruments.dust.update(&lander, terrain) {
+                println!("{}", message);
+            }
+            if contact_light.is_none() && height_above_terrain(&lander, terrain) <= PROBE_LENGTH {
+                println!("\x1b[34mCONTACT LIGHT at t={:.2}s: a surface probe has touched.\x1b[0m", lander.current_time);
+                contact_light = Some((lander.current_time, lander.v_down));
+                recorder.log_event(&lander, &instruments, "Contact light");
+                if segment.autopilot {
+                    println!("Autopilot: ENGINE STOP.");
+                    recorder.log_event(&lander, &instruments, "Engine stop (autopilot)");
+                    engine_stopped = true;
+                } else if !engine_stopped && height_above_terrain(&lander, terrain) > 0.0 {
+                    // Hand control back to the pilot at once for the engine stop
+                    println!("Burn interrupted. Enter \"stop\" to shut down the descent engine.");
+                    if !scheduled_segments.is_empty() {
+                        println!("Schedule cancelled. {} segment(s) dropped.", scheduled_segments.len());
+                        scheduled_segments.clear();
+                    }
+                    contact_interrupt = true;
+                }
+            }
+            if let (Some((contact_time, _)), None) = (contact_light, engine_off_time) {
+                if lander.dps_level < ENGINE_OFF_LEVEL {
+                    // The engine was off for the whole step just flown
+                    engine_off_time = Some(before.current_time.max(contact_time));
+                }
+            }
+            if (lander.current_time * 10.0).round() as i64 % 10 == 0 {
+                recorder.record(&lander, &instruments);
+            }
+            if instruments.gauge.low_level_tripped(&lander, &vehicle) {
+                println!("\x1b[31mLOW LEVEL light at t={:.2}s: descent propellant below {:.1}% of the full load.\x1b[0m",
+                    lander.current_time, vehicle.low_level_fraction * 100.0);
+            }
+            if instruments.gauge.enabled {
+                // Mission Control watches the indicated quantity and calls the hover time left
+                let hover_time = instruments.gauge.read(&lander, &vehicle) / hover_burn_rate(lander.mass);
+                if let Some(&seconds) = instruments.gauge.callouts.first() {
+                    if hover_time <= seconds {
+                        instruments.gauge.callouts.remove(0);
+                        send_message(&mut pending_messages, lander.current_time, message_delay, MessageKind::Callout(seconds));
+                    }
+                }
+            }
 
             // Provide Feedback after time delay
             let mut j = 0;
-            while j < pending_corrections.len() {
-                if current_time >= pending_corrections[j].display_time {
-                    if pending_corrections[j].is_confirmation == 1 {
-                        println!("\x1b[33m[Mission Control: on t={:.2}s (received on t={:.2}s)] Burn rates are nominal.\x1b[0m",
-                            pending_corrections[j].eval_time, pending_corrections[j].display_time);
-                    } else {
-                        if pending_corrections[j].v_burn_diff > 0.0 {
-                            println!("\x1b[33m[Mission Control: on t={:.2}s (received on t={:.2}s)] Increase vBurn by {:.2} lbs/s\x1b[0m",
-                                pending_corrections[j].eval_time, pending_corrections[j].display_time, pending_corrections[j].v_burn_diff);
-                        } else if pending_corrections[j].v_burn_diff < 0.0 {
-                            println!("\x1b[33m[Mission Control: on t={:.2}s (received on t={:.2}s)] Decrease vBurn by {:.2} lbs/s\x1b[0m",
-                                pending_corrections[j].eval_time, pending_corrections[j].display_time, -pending_corrections[j].v_burn_diff);
-                        }
-                        if pending_corrections[j].h_burn_diff > 0.0 {
-                            println!("\x1b[33m[Mission Control: on t={:.2}s (received on t={:.2}s)] Increase hBurn by {:.2} lbs/s\x1b[0m",
-                                pending_corrections[j].eval_time, pending_corrections[j].display_time, pending_corrections[j].h_burn_diff);
-                        } else if pending_corrections[j].h_burn_diff < 0.0 {
-                            println!("\x1b[33m[Mission Control: on t={:.2}s (received on t={:.2}s)] Decrease hBurn by {:.2} lbs/s\x1b[0m",
-                                pending_corrections[j].eval_time, pending_corrections[j].display_time, -pending_corrections[j].h_burn_diff);
+            while j < pending_messages.len() {
+                // Messages wait while communications are down
+                if lander.current_time < pending_messages[j].display_time || failures.comm_lost() {
+                    j += 1;
+                    continue;
+                }
+                let message = pending_messages.remove(j);
+                let header = format!("[Mission Control: on t={:.2}s (received on t={:.2}s)]", message.eval_time, message.display_time);
+                match message.kind {
+                    MessageKind::ProgramAlarm(alarm) if alarm.go => {
+                        println!("\x1b[33m{} We're GO on that {} alarm.\x1b[0m", header, alarm.code);
+                        recorder.log_event(&lander, &instruments, &format!("Mission Control: GO on the {} alarm", alarm.code));
+                    }
+                    MessageKind::ProgramAlarm(alarm) => {
+                        println!("\x1b[31m{} NO-GO on the AGC: the {} alarms are coming too often. Take over manually.\x1b[0m",
+                            header, alarm.code);
+                        if !agc.failed {
+                            agc.failed = true;
+                            agc.in_control = false;
+                            recorder.log_event(&lander, &instruments, "Mission Control: NO-GO on the AGC, manual control");
+                            if segment.autopilot {
+                                println!("Burn interrupted. The AGC is down: fly the LEM manually.");
+                                agc_interrupt = true;
+                            }
+                        }
+                    }
+                    MessageKind::Callout(seconds) => {
+                        println!("\x1b[33m{} {:.0} seconds\x1b[0m", header, seconds);
+                    }
+                    MessageKind::WhatIf(what_if) => {
+                        report_what_if(&what_if, message.eval_time, &format!("{} What-if", header));
+                    }
+                    MessageKind::Confirmation { landing_miss } => {
+                        report_landing_miss(&header, landing_miss);
+                        println!("\x1b[33m{} Burn rates are nominal.\x1b[0m", header);
+                    }
+                    MessageKind::Correction { v_burn_diff, h_burn_diff, c_burn_diff, landing_miss } => {
+                        report_landing_miss(&header, landing_miss);
+                        if v_burn_diff > 0.0 {
+                            println!("\x1b[33m{} Increase vBurn by {:.2} lbs/s\x1b[0m", header, v_burn_diff);
+                        } else if v_burn_diff < 0.0 {
+                            println!("\x1b[33m{} Decrease vBurn by {:.2} lbs/s\x1b[0m", header, -v_burn_diff);
+                        }
+                        if h_burn_diff > 0.0 {
+                            println!("\x1b[33m{} Increase hBurn by {:.2} lbs/s\x1b[0m", header, h_burn_diff);
+                        } else if h_burn_diff < 0.0 {
+                            println!("\x1b[33m{} Decrease hBurn by {:.2} lbs/s\x1b[0m", header, -h_burn_diff);
+                        }
+                        if c_burn_diff > 0.0 {
+                            println!("\x1b[33m{} Increase cBurn by {:.2} lbs/s\x1b[0m", header, c_burn_diff);
+                        } else if c_burn_diff < 0.0 {
+                            println!("\x1b[33m{} Decrease cBurn by {:.2} lbs/s\x1b[0m", header, -c_burn_diff);
                         }
                     }
-                    pending_corrections.remove(j);
-                } else {
-                    j += 1;
                 }
             }
-        }
 
-        // Step 6: Queue New Correction or Confirmation
-        if altitude > 0.0 {
-            // Temporary variables for projection
-            let mut projected_altitude = altitude;
-            let mut projected_v_down = v_down;
-            let mut projected_h_speed = horiz_speed;
-            let mut temp_mass = mass;
-            let mut temp_fuel = descent_fuel_mass;
-            let mut temp_mass_slugs = temp_mass / standard_gravity;
-            let projection_dt = 0.1;  // Small time step for projection
-            let max_projection_steps = 10000;  // Limit iterations to prevent infinite loop
-            let mut step_count = 0;
-
-            // Simulate descent until landing or limit reached
-            while projected_altitude > 0.0 && temp_fuel >= 0.0 && temp_mass > 0.0 && step_count < max_projection_steps {
-                // Fuel consumption
-                let fuel_used = (v_burn + h_burn.abs()) * projection_dt;
-                let fuel_used = if fuel_used > temp_fuel { temp_fuel } else { fuel_used };
-                temp_fuel -= fuel_used;
-                temp_mass = descent_dry_mass + temp_fuel + ascent_dry_mass + ascent_fuel_mass;
-                if temp_mass <= 0.0 {
-                    break;  // Avoid division by zero
+            if let Some(condition) = segment.until {
+                if condition.is_met(&instruments.nav.estimate(&lander)) {
+                    println!("Condition {} met at t={:.2}s.", condition.describe(), lander.current_time);
+                    condition_met = true;
                 }
-                temp_mass_slugs = temp_mass / standard_gravity;
-
-                // Vertical acceleration
-                let mass_flow_rate_vertical = v_burn / standard_gravity;  // slugs/s
-                let thrust_force_vertical = mass_flow_rate_vertical * vex;  // lbs
-                let thrust_acc_vertical = thrust_force_vertical / temp_mass_slugs;  // ft/s²
-                let net_acc_vertical = gravity - thrust_acc_vertical;
-
-                // Horizontal acceleration
-                let mass_flow_rate_horizontal = h_burn.abs() / standard_gravity;
-                let thrust_force_horizontal = mass_flow_rate_horizontal * vex;
-                let thrust_acc_horizontal = (if h_burn >= 0.0 { 1.0 } else { -1.0 }) * thrust_force_horizontal / temp_mass_slugs;
-
-                // Update state using RK4
-                rk4_vertical(&mut projected_altitude, &mut projected_v_down, projection_dt, net_acc_vertical);
-                // Note: In C, horizPos is updated but not used in feedback; we update a dummy value
-                let mut dummy_pos = 0.0;
-                rk4_horizontal(&mut dummy_pos, &mut projected_h_speed, projection_dt, thrust_acc_horizontal);
-                step_count += 1;
             }
+        }
+        if let Some(condition) = segment.until {
+            if !condition_met && height_above_terrain(&lander, terrain) > 0.0 {
+                println!("\x1b[31mSafety timeout: {} not reached after {:.1} s.\x1b[0m", condition.describe(), duration);
+            }
+        }
+
+        // Step 6: Queue New Correction or Confirmation (Mission Control needs telemetry)
+        if height_above_terrain(&lander, terrain) > 0.0 && failures.comm_lost() {
+            println!("\x1b[33m[No communications: Mission Control cannot see this burn]\x1b[0m");
+        } else if height_above_terrain(&lander, terrain) > 0.0 && !segment.autopilot {
+            // Mission Control works from the state the LEM's computer reports (no burn advice while the AGC flies)
+            let projection = project_burn(&instruments.nav.estimate(&lander), &vehicle, &moon, v_burn, h_burn, c_burn, f64::INFINITY);
+            let projected_v_down = projection.state.v_down;
+            let projected_h_speed = projection.state.horiz_speed;
+            let projected_c_speed = projection.state.cross_speed;
+            let landing_miss = if projection.landed {
+                Some((projection.state.horiz_pos - site.0, projection.state.cross_pos - site.1))
+            } else {
+                None
+            };
 
             // Define safe landing targets
             let target_touchdown_speed = 5.0;  // ft/s
@@ -384,68 +3742,236 @@ fn main() {
             // Calculate errors from safe landing targets
             let v_down_error = projected_v_down - target_touchdown_speed;  // Positive if too fast
             let h_speed_error = projected_h_speed - target_h_speed;        // Positive if right, negative if left
+            let c_speed_error = projected_c_speed - target_h_speed;        // Crossrange, same target
 
             // Suggest burn adjustments (tuned proportionality constants)
             let mut v_burn_adjustment = v_down_error * 0.1;    // lbs/s per ft/s error
             let mut h_burn_adjustment = h_speed_error * 0.05;  // Smaller factor for horizontal
+            let mut c_burn_adjustment = -c_speed_error * 0.05;  // cBurn opposes the crossrange drift
 
             // Clamp adjustments to physical limits
-            if v_burn + v_burn_adjustment > max_vertical_burn {
-                v_burn_adjustment = max_vertical_burn - v_burn;
+            if v_burn + v_burn_adjustment > MAX_VERTICAL_BURN {
+                v_burn_adjustment = MAX_VERTICAL_BURN - v_burn;
             }
             if v_burn + v_burn_adjustment < 0.0 {
                 v_burn_adjustment = -v_burn;
             }
-            if h_burn + h_burn_adjustment > max_horiz_burn {
-                h_burn_adjustment = max_horiz_burn - h_burn;
+            // Only advise settings the DPS can hold
+            v_burn_adjustment = clamp_to_envelope(v_burn + v_burn_adjustment, &vehicle) - v_burn;
+            if h_burn + h_burn_adjustment > MAX_HORIZ_BURN {
+                h_burn_adjustment = MAX_HORIZ_BURN - h_burn;
             }
             // Fixed from C code: Original had 'hBurnAdjustment = -hBurn + maxHorizBurn', which was incorrect
-            if h_burn + h_burn_adjustment < -max_horiz_burn {
-                h_burn_adjustment = -max_horiz_burn - h_burn;
+            if h_burn + h_burn_adjustment < -MAX_HORIZ_BURN {
+                h_burn_adjustment = -MAX_HORIZ_BURN - h_burn;
             }
+            c_burn_adjustment = (c_burn + c_burn_adjustment).clamp(-MAX_HORIZ_BURN, MAX_HORIZ_BURN) - c_burn;
 
             // Queue feedback
-            if v_down_error.abs() <= tolerance && h_speed_error.abs() <= tolerance {
-                if pending_corrections.len() < 100 {
-                    pending_corrections.push(Correction {
-                        eval_time: current_time,
-                        display_time: current_time + transmission_delay + processing_delay,
-                        v_burn_diff: 0.0,
-                        h_burn_diff: 0.0,
-                        is_confirmation: 1,
-                    });
-                }
+            let kind = if v_down_error.abs() <= tolerance && h_speed_error.hypot(c_speed_error) <= tolerance {
+                MessageKind::Confirmation { landing_miss }
             } else {
-                if pending_corrections.len() < 100 {
-                    pending_corrections.push(Correction {
-                        eval_time: current_time,
-                        display_time: current_time + transmission_delay + processing_delay,
-                        v_burn_diff: v_burn_adjustment,
-                        h_burn_diff: h_burn_adjustment,
-                        is_confirmation: 0,
-                    });
+                MessageKind::Correction {
+                    v_burn_diff: v_burn_adjustment,
+                    h_burn_diff: h_burn_adjustment,
+                    c_burn_diff: c_burn_adjustment,
+                    landing_miss,
                 }
-            }
+            };
+            send_message(&mut pending_messages, lander.current_time, message_delay, kind);
         }
     }
 
     // Evaluate Landing Outcome 
-    if altitude <= 0.0 {
-        if altitude < 0.0 {
-            altitude = 0.0;
-        }
+    if height_above_terrain(&lander, terrain) <= 0.0 {
+        lander.altitude = terrain.elevation(lander.horiz_pos, lander.cross_pos);
         println!();
-        println!("Touchdown at t = {:.1} s", current_time);
-        println!("Final Downward Speed: {:.2} ft/s", v_down);
-        println!("Final Horizontal Speed: {:.2} ft/s", horiz_speed);
-        if v_down <= target_touchdown_speed && horiz_speed.abs() <= 5.0 {
+        println!("Touchdown at t = {:.1} s", lander.current_time);
+        println!("Final Downward Speed: {:.2} ft/s", lander.v_down);
+        println!("Final Horizontal Speed: {:.2} ft/s", lander.horiz_speed);
+        println!("Final Crossrange Speed: {:.2} ft/s", lander.cross_speed);
+        let final_ground_speed = ground_speed(&lander);
+        println!("Final Total Horizontal Speed: {:.2} ft/s", final_ground_speed);
+        let slope = terrain.slope(lander.horiz_pos, lander.cross_pos);
+        let boulder = terrain.tallest_boulder(lander.horiz_pos, lander.cross_pos, GEAR_RADIUS);
+        if !terrain.is_flat() {
+            println!("Surface: elevation {:.1} ft, slope {:.1} deg, tallest boulder under the gear {:.1} ft",
+                lander.altitude, slope, boulder);
+        }
+        let gear = touchdown_dynamics(&lander, terrain);
+        println!("Landing gear: footpads sank {:.1} in, primary struts stroked {:.1} in (of {:.0} in){}",
+            gear.sinkage * 12.0, gear.stroke * 12.0, STRUT_STROKE * 12.0, if gear.bottomed { ", BOTTOMED OUT" } else { "" });
+        let ground = if gear.downhill.abs() < 0.05 {
+            "level ground".to_string()
+        } else {
+            format!("{:.1} deg {}", gear.downhill.abs(), if gear.downhill > 0.0 { "downhill" } else { "uphill" })
+        };
+        println!("Stability: tipping energy {:.0} of {:.0} ft-lbf needed to tip over ({} in the tipping direction)",
+            gear.tip_energy, gear.tip_barrier, ground);
+        // Engine run after contact light; an engine still firing at touchdown is stopped a reaction time later
+        let mut judged_v_down = lander.v_down;
+        let mut engine_run = 0.0;
+        if let Some((contact_time, contact_v_down)) = contact_light {
+            engine_run = match engine_off_time {
+                Some(off_time) => off_time - contact_time,
+                None => lander.current_time + CREW_REACTION - contact_time,
+            };
+            let grade = if engine_off_time.is_none() {
+                "\x1b[33mengine still firing at touchdown\x1b[0m"
+            } else if engine_run <= ENGINE_STOP_PROMPT {
+                "by the book"
+            } else {
+                "\x1b[33mlate\x1b[0m"
+            };
+            println!("Engine stop: {:.1} s after contact light ({}); descent rate at contact light {:.2} ft/s",
+                engine_run, grade, contact_v_down);
+            // After a proper engine stop the drop from probe height is expected, so judge the descent at contact
+            if engine_off_time.is_some() && engine_run <= ENGINE_STOP_PROMPT {
+                judged_v_down = contact_v_down;
+            }
+        }
+        // The gear compresses as it strokes and sinks, so boulders come closer to the descent stage
+        let clearance = (BOULDER_CLEARANCE - gear.stroke - gear.sinkage).max(0.0);
+        if gear.bottomed {
+            println!("\x1b[31mCrash Landing! Impact speed is too high. The landing gear bottomed out and the descent stage hit the surface.\x1b[0m");
+        } else if gear.tipped {
+            println!("\x1b[31mTip-over! The LEM tipped over at {:.1} ft/s ({} in the tipping direction).\x1b[0m", final_ground_speed, ground);
+        } else if boulder > 0.0 && boulder > clearance {
+            println!("\x1b[31mLanding damage! A {:.1} ft boulder struck the descent stage (clearance {:.1} ft after the legs stroked).\x1b[0m",
+                boulder, clearance);
+        } else if engine_run > NOZZLE_DAMAGE_TIME {
+            println!("\x1b[31mNozzle damage! The descent engine ran {:.1} s after contact light; blowback from the surface damaged the nozzle.\x1b[0m",
+                engine_run);
+        } else if judged_v_down <= target_touchdown_speed && final_ground_speed <= 5.0 {
             println!("\x1b[32mPerfect Landing! Impact speed is safe.\x1b[0m");
-        } else if v_down <= 15.0 && horiz_speed.abs() <= 15.0 {
-            println!("\x1b[32mGood Landing (minor impact).\x1b[0m");
         } else {
-            println!("\x1b[31mCrash Landing! Impact speed is too high.\x1b[0m");
+            println!("\x1b[32mGood Landing (minor impact).\x1b[0m");
+        }
+        let (downrange, crossrange) = distance_to_go(&lander, site);
+        let miss = downrange.hypot(crossrange);
+        println!("Landing Site Miss: {:.0} ft ({:.0} ft {}, {:.0} ft {})", miss,
+            downrange.abs(), if downrange <= 0.0 { "long" } else { "short" },
+            crossrange.abs(), if crossrange <= 0.0 { "right" } else { "left" });
+        println!("Landing Accuracy: {}", accuracy_grade(miss));
+        if site != (scenario.target_pos, scenario.target_cross) {
+            let (planned_x, planned_y) = distance_to_go(&lander, (scenario.target_pos, scenario.target_cross));
+            println!("Distance from the originally planned site: {:.0} ft (site redesignated)", planned_x.hypot(planned_y));
         }
-    } else if current_time >= time_limit {
+    } else if lander.current_time >= time_limit {
         println!("\nSimulation aborted after reaching the time limit.");
     }
-}
\ No newline at end of file
+    if instruments.nav.enabled {
+        recorder.print_debrief();
+    }
+    if failures.enabled() || agc.enabled {
+        recorder.print_flight_log();
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII_GRID: &str = "ncols 3\nnrows 2\nxllcorner 100\nyllcorner 200\ncellsize 5\nNODATA_value -9999\n1 2 3\n4 5 6\n";
+
+    #[test]
+    fn ascii_grid_valid() {
+        let grid = ElevationGrid::parse_ascii_grid(ASCII_GRID).unwrap();
+        assert_eq!((grid.ncols, grid.nrows), (3, 2));
+        assert_eq!((grid.x_origin, grid.y_top, grid.cell_size), (100.0, 210.0, 5.0));
+        assert_eq!(grid.values, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn ascii_grid_truncated() {
+        let text = ASCII_GRID.trim_end().trim_end_matches('6');
+        assert!(matches!(ElevationGrid::parse_ascii_grid(text), Err(message) if message.contains("expected 6 elevations")));
+    }
+
+    #[test]
+    fn ascii_grid_oversized_header() {
+        let text = "ncols 1000000000000\nnrows 1000000000000\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2 3 4\n";
+        assert!(ElevationGrid::parse_ascii_grid(text).is_err());
+        let text = "ncols 100000\nnrows 100000\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2 3 4\n";
+        assert!(ElevationGrid::parse_ascii_grid(text).is_err());
+    }
+
+    // Minimal single-strip GeoTIFF of signed 16-bit samples, georeferenced at (1000, 3000) m with 2 m pixels
+    fn geotiff(little: bool, ncols: u32, nrows: u32, rows_per_strip: u32, samples: &[i16]) -> Vec<u8> {
+        let put = |bytes: &mut Vec<u8>, value: u64, size: usize| {
+            for i in 0..size {
+                let shift = if little { 8 * i } else { 8 * (size - 1 - i) };
+                bytes.push((value >> shift) as u8);
+            }
+        };
+        let entries = 9;
+        let scale_at = 8 + 2 + 12 * entries + 4;
+        let tiepoint_at = scale_at + 3 * 8;
+        let data_at = tiepoint_at + 6 * 8;
+        let mut bytes = if little { b"II".to_vec() } else { b"MM".to_vec() };
+        put(&mut bytes, 42, 2);
+        put(&mut bytes, 8, 4);
+        put(&mut bytes, entries, 2);
+        for (tag, field_type, count, value) in [
+            (256, 4, 1, ncols as u64),
+            (257, 4, 1, nrows as u64),
+            (258, 3, 1, 16),
+            (273, 4, 1, data_at),
+            (277, 3, 1, 1),
+            (278, 4, 1, rows_per_strip as u64),
+            (339, 3, 1, 2),
+            (33550, 12, 3, scale_at),
+            (33922, 12, 6, tiepoint_at),
+        ] {
+            put(&mut bytes, tag, 2);
+            put(&mut bytes, field_type, 2);
+            put(&mut bytes, count, 4);
+            if field_type == 3 {
+                put(&mut bytes, value, 2);
+                put(&mut bytes, 0, 2);
+            } else {
+                put(&mut bytes, value, 4);
+            }
+        }
+        put(&mut bytes, 0, 4);
+        for number in [2.0f64, 2.0, 0.0, 0.0, 0.0, 0.0, 1000.0, 3000.0, 0.0] {
+            put(&mut bytes, number.to_bits(), 8);
+        }
+        for &sample in samples {
+            put(&mut bytes, sample as u16 as u64, 2);
+        }
+        bytes
+    }
+
+    #[test]
+    fn geotiff_little_endian() {
+        let grid = ElevationGrid::parse_geotiff(&geotiff(true, 3, 2, 2, &[1, -2, 3, 4, 5, -6])).unwrap();
+        assert_eq!((grid.ncols, grid.nrows), (3, 2));
+        assert_eq!((grid.x_origin, grid.y_top, grid.cell_size), (1000.0, 3000.0, 2.0));
+        assert_eq!(grid.values, vec![1.0, -2.0, 3.0, 4.0, 5.0, -6.0]);
+    }
+
+    #[test]
+    fn geotiff_big_endian() {
+        let grid = ElevationGrid::parse_geotiff(&geotiff(false, 3, 2, 2, &[1, -2, 3, 4, 5, -6])).unwrap();
+        assert_eq!(grid.values, vec![1.0, -2.0, 3.0, 4.0, 5.0, -6.0]);
+    }
+
+    #[test]
+    fn geotiff_truncated() {
+        let bytes = geotiff(true, 3, 2, 2, &[1, 2, 3, 4, 5, 6]);
+        assert!(ElevationGrid::parse_geotiff(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ElevationGrid::parse_geotiff(&bytes[..20]).is_err());
+    }
+
+    #[test]
+    fn geotiff_rows_per_strip_zero() {
+        let bytes = geotiff(true, 3, 2, 0, &[1, 2, 3, 4, 5, 6]);
+        assert!(matches!(ElevationGrid::parse_geotiff(&bytes), Err(message) if message.contains("RowsPerStrip")));
+    }
+
+    #[test]
+    fn geotiff_oversized_header() {
+        let bytes = geotiff(true, 4_000_000_000, 4_000_000_000, 1, &[1, 2, 3, 4]);
+        assert!(ElevationGrid::parse_geotiff(&bytes).is_err());
+    }
+}